@@ -0,0 +1,77 @@
+//! Checks the register map in `src/device.rs` at build time.
+//!
+//! All KSZ8851SNL registers are 16 bits wide at 2-aligned addresses, which is all the
+//! register interface accesses (the low half or the high half of a 4-aligned word). A
+//! register added at an odd address would otherwise only show up as a panic at runtime.
+//!
+//! It also checks `TxCtrlWord::frame_id` is the width the driver assumes, and that the
+//! wake-up frame registers follow the layout `Chip::configure_wakeup_frame` writes to.
+use std::fs;
+
+// Not real registers, see their definitions
+const PSEUDO_REGISTERS: &[&str] = &["TxCtrlWord"];
+
+// Each wake-up frame `n` has these registers, in order, from 0x30 + 0x10 * n
+const WAKEUP_FRAME_REGISTERS: [&str; 6] = ["CRC0", "CRC1", "BM0", "BM1", "BM2", "BM3"];
+
+// The address `name` should be at if it's a wake-up frame register, e.g. `WF1BM2`
+fn wakeup_frame_address(name: &str) -> Option<u8> {
+    let rest = name.strip_prefix("WF")?;
+    let frame = rest.get(..1)?.parse::<u8>().ok()?;
+    let index = WAKEUP_FRAME_REGISTERS
+        .iter()
+        .position(|r| *r == &rest[1..])? as u8;
+    Some(0x30 + 0x10 * frame + 2 * index)
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=src/device.rs");
+    let dsl = fs::read_to_string("src/device.rs").expect("reading src/device.rs");
+
+    let mut register = None;
+    // `register` is taken once its address is checked, this stays until the next one
+    let mut current = None;
+    for line in dsl.lines().map(str::trim) {
+        if current == Some("TxCtrlWord") && line.starts_with("frame_id:") {
+            // The driver wraps frame IDs at `driver::MAX_FRAME_ID`
+            assert!(
+                line.ends_with("= 0..=5,"),
+                "TxCtrlWord::frame_id is no longer 6 bits, update MAX_FRAME_ID: {line}",
+            );
+        }
+        if let Some(name) = current.filter(|n| wakeup_frame_address(n).is_some()) {
+            // The single field of a wake-up frame register is named after it
+            if let Some((field, _)) = line.split_once(": RW") {
+                assert!(
+                    field == name.to_lowercase(),
+                    "{name}: field {field} should be named {}",
+                    name.to_lowercase(),
+                );
+            }
+        }
+        if let Some(rest) = line.strip_prefix("register ") {
+            register = rest.split_whitespace().next();
+            current = register;
+        } else if let Some(value) = line
+            .strip_prefix("const ADDRESS = ")
+            .and_then(|v| v.strip_suffix(';'))
+        {
+            let name = register.take().expect("ADDRESS outside a register");
+            if PSEUDO_REGISTERS.contains(&name) {
+                continue;
+            }
+            let address = u8::from_str_radix(value.trim_start_matches("0x"), 16)
+                .unwrap_or_else(|_| panic!("{name}: can't parse address {value}"));
+            assert!(
+                address & 0b11 == 0 || address & 0b11 == 2,
+                "{name}: address {address:#04x} isn't 2-aligned, so can't be accessed",
+            );
+            if let Some(expected) = wakeup_frame_address(name) {
+                assert!(
+                    address == expected,
+                    "{name}: address {address:#04x} should be {expected:#04x}",
+                );
+            }
+        }
+    }
+}
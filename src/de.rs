@@ -0,0 +1,208 @@
+//! `serde::Deserialize` for [`InitConfig`] and the types in it, behind the `serde`
+//! feature, e.g. to load the configuration from a file on embedded Linux.
+//!
+//! Fields left out take their default: [`InitConfig::new`] and [`RxFilterConfig::new`]
+//! for those structs, and `false` (disabled) for each [`InterruptMask`] bit. Unknown
+//! fields are an error. [`SpiRxDataBurstLength`] is one of its variant names, e.g.
+//! `"SingleFrame"`.
+//!
+//! The impls are written by hand as `serde_derive` isn't a dependency.
+use crate::device::SpiRxDataBurstLength;
+use crate::driver::{InitConfig, InterruptMask, RxFilterConfig};
+use core::fmt;
+use serde::Deserialize;
+use serde::de::{
+    self, DeserializeSeed, Deserializer, EnumAccess, MapAccess, VariantAccess, Visitor,
+};
+
+// Deserializes a field or variant name into the matching entry of `names`
+struct NameSeed(&'static [&'static str]);
+
+impl<'de> DeserializeSeed<'de> for NameSeed {
+    type Value = &'static str;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_identifier(self)
+    }
+}
+
+impl Visitor<'_> for NameSeed {
+    type Value = &'static str;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "one of {:?}", self.0)
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        self.0
+            .iter()
+            .find(|name| **name == v)
+            .copied()
+            .ok_or_else(|| E::unknown_field(v, self.0))
+    }
+}
+
+// A struct deserialized field by field over `$default`
+macro_rules! deserialize_struct {
+    ($ty:ident, $default:expr, [$($field:ident),* $(,)?]) => {
+        impl<'de> Deserialize<'de> for $ty {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                const FIELDS: &[&str] = &[$(stringify!($field)),*];
+
+                struct StructVisitor;
+
+                impl<'de> Visitor<'de> for StructVisitor {
+                    type Value = $ty;
+
+                    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                        f.write_str(concat!("struct ", stringify!($ty)))
+                    }
+
+                    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<$ty, A::Error> {
+                        let mut value: $ty = $default;
+                        while let Some(field) = map.next_key_seed(NameSeed(FIELDS))? {
+                            $(
+                                if field == stringify!($field) {
+                                    value.$field = map.next_value()?;
+                                    continue;
+                                }
+                            )*
+                        }
+                        Ok(value)
+                    }
+                }
+
+                deserializer.deserialize_struct(stringify!($ty), FIELDS, StructVisitor)
+            }
+        }
+    };
+}
+
+deserialize_struct!(
+    InitConfig,
+    InitConfig::new(),
+    [
+        rx_filter,
+        tx_auto_pad,
+        tx_auto_crc,
+        flow_control,
+        rx_source_address_filter,
+        verify_mac_writes,
+        rx_checksum_verification,
+        tx_checksum_generation,
+        rx_ip_header_offset,
+        rx_burst_length,
+        interrupts,
+    ]
+);
+
+deserialize_struct!(
+    RxFilterConfig,
+    RxFilterConfig::new(),
+    [broadcast, multicast, unicast, all]
+);
+
+deserialize_struct!(
+    InterruptMask,
+    InterruptMask::default(),
+    [
+        link_changed,
+        tx_complete,
+        rx_ready,
+        rx_overrun,
+        tx_stopped,
+        rx_stopped,
+        tx_space_available,
+        wakeup_frame_detected,
+        magic_packet_detected,
+        linkup_detected,
+        energy_detected,
+        delayed_energy_detected,
+        spi_bus_error,
+    ]
+);
+
+impl<'de> Deserialize<'de> for SpiRxDataBurstLength {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // The reserved values aren't accepted, `Chip::init_with_config` would reject them
+        const VARIANTS: &[&str] = &["X4Bytes", "X8Bytes", "X16Bytes", "X32Bytes", "SingleFrame"];
+
+        struct EnumVisitor;
+
+        impl<'de> Visitor<'de> for EnumVisitor {
+            type Value = SpiRxDataBurstLength;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("enum SpiRxDataBurstLength")
+            }
+
+            fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<Self::Value, A::Error> {
+                let (name, variant) = data.variant_seed(NameSeed(VARIANTS))?;
+                variant.unit_variant()?;
+                Ok(match name {
+                    "X4Bytes" => SpiRxDataBurstLength::X4Bytes,
+                    "X8Bytes" => SpiRxDataBurstLength::X8Bytes,
+                    "X16Bytes" => SpiRxDataBurstLength::X16Bytes,
+                    "X32Bytes" => SpiRxDataBurstLength::X32Bytes,
+                    _ => SpiRxDataBurstLength::SingleFrame,
+                })
+            }
+        }
+
+        deserializer.deserialize_enum("SpiRxDataBurstLength", VARIANTS, EnumVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_is_default() {
+        let config: InitConfig = serde_json::from_str("{}").unwrap();
+        assert_eq!(config, InitConfig::default());
+    }
+
+    #[test]
+    fn fields_override_defaults() {
+        let config: InitConfig = serde_json::from_str(
+            r#"{
+                "rx_filter": { "multicast": true },
+                "flow_control": false,
+                "rx_checksum_verification": true,
+                "rx_burst_length": "X32Bytes",
+                "interrupts": { "rx_ready": true, "link_changed": true }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config,
+            InitConfig {
+                rx_filter: RxFilterConfig {
+                    multicast: true,
+                    ..RxFilterConfig::new()
+                },
+                flow_control: false,
+                rx_checksum_verification: true,
+                rx_burst_length: SpiRxDataBurstLength::X32Bytes,
+                interrupts: InterruptMask {
+                    rx_ready: true,
+                    link_changed: true,
+                    ..Default::default()
+                },
+                ..InitConfig::new()
+            }
+        );
+    }
+
+    #[test]
+    fn unknown_fields_and_variants() {
+        assert!(serde_json::from_str::<InitConfig>(r#"{ "mac": [2, 0, 0, 0, 0, 1] }"#).is_err());
+        assert!(
+            serde_json::from_str::<InitConfig>(r#"{ "rx_burst_length": "X64Bytes" }"#).is_err()
+        );
+        assert!(
+            serde_json::from_str::<InitConfig>(r#"{ "rx_burst_length": "Reserved" }"#).is_err()
+        );
+    }
+}
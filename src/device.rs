@@ -1,6 +1,6 @@
 use embedded_hal::spi::Operation;
 
-use crate::driver::{Opcode, reg_cmd};
+use crate::raw::{Opcode, reg_cmd};
 
 device_driver::create_device!(
     device_name: Ksz8851snl,
@@ -188,7 +188,7 @@ device_driver::create_device!(
             const SIZE_BITS = 16;
 
             /// Wake up Frame 0 Byte Mask 2
-            /// The next 16 bytes mask covering bytes 33 to 48 of a Wake-up frame 0 pattern.
+            /// The next 16 bytes mask covering bytes 33 to 48 of a Wake up frame 0 pattern.
             wf0bm2: RW uint = 0..=15,
         },
         /// Wakeup Frame 0 Byte Mask 3 Register
@@ -197,8 +197,8 @@ device_driver::create_device!(
             const SIZE_BITS = 16;
 
             /// Wake up Frame 0 Byte Mask 3
-            /// The last 16 bytes mask covering bytes 49 to 64 of a Wake-up frame 0 pattern.
-            wf0bm2: RW uint = 0..=15,
+            /// The last 16 bytes mask covering bytes 49 to 64 of a Wake up frame 0 pattern.
+            wf0bm3: RW uint = 0..=15,
         },
 
         // Wakeup frame 1
@@ -208,7 +208,7 @@ device_driver::create_device!(
             const ADDRESS = 0x40;
             const SIZE_BITS = 16;
             /// Wake up Frame 1 CRC (lower 16 bits)
-            /// The expected CRC value of a Wake up frame 0 pattern
+            /// The expected CRC value of a Wake up frame 1 pattern
             wf1crc0: RW uint = 0..=15,
         },
         /// Wakeup Frame 1 CRC1 Register
@@ -216,7 +216,7 @@ device_driver::create_device!(
             const ADDRESS = 0x42;
             const SIZE_BITS = 16;
             /// Wake up Frame 1 CRC (upper 16 bits)
-            /// The expected CRC value of a Wake up frame 0 pattern
+            /// The expected CRC value of a Wake up frame 1 pattern
             wf1crc1: RW uint = 0..=15,
         },
         /// Wakeup Frame 1 Byte Mask 0 Register
@@ -225,7 +225,7 @@ device_driver::create_device!(
             const SIZE_BITS = 16;
 
             /// Wake up Frame 1 Byte Mask 0
-            /// The first 16 bytes mask of a Wake up frame 0 pattern.
+            /// The first 16 bytes mask of a Wake up frame 1 pattern.
             wf1bm0: RW uint = 0..=15,
         },
         /// Wakeup Frame 1 Byte Mask 1 Register
@@ -234,7 +234,7 @@ device_driver::create_device!(
             const SIZE_BITS = 16;
 
             /// Wake up Frame 1 Byte Mask 1
-            /// The next 16 bytes mask covering bytes 17 to 32 of a Wake up frame 0 pattern.
+            /// The next 16 bytes mask covering bytes 17 to 32 of a Wake up frame 1 pattern.
             wf1bm1: RW uint = 0..=15,
         },
         /// Wakeup Frame 1 Byte Mask 2 Register
@@ -243,7 +243,7 @@ device_driver::create_device!(
             const SIZE_BITS = 16;
 
             /// Wake up Frame 1 Byte Mask 2
-            /// The next 16 bytes mask covering bytes 33 to 48 of a Wake-up frame 0 pattern.
+            /// The next 16 bytes mask covering bytes 33 to 48 of a Wake up frame 1 pattern.
             wf1bm2: RW uint = 0..=15,
         },
         /// Wakeup Frame 1 Byte Mask 3 Register
@@ -252,8 +252,8 @@ device_driver::create_device!(
             const SIZE_BITS = 16;
 
             /// Wake up Frame 1 Byte Mask 3
-            /// The last 16 bytes mask covering bytes 49 to 64 of a Wake-up frame 0 pattern.
-            wf1bm2: RW uint = 0..=15,
+            /// The last 16 bytes mask covering bytes 49 to 64 of a Wake up frame 1 pattern.
+            wf1bm3: RW uint = 0..=15,
         },
 
         // Wakeup frame 2
@@ -263,7 +263,7 @@ device_driver::create_device!(
             const ADDRESS = 0x50;
             const SIZE_BITS = 16;
             /// Wake up Frame 2 CRC (lower 16 bits)
-            /// The expected CRC value of a Wake up frame 0 pattern
+            /// The expected CRC value of a Wake up frame 2 pattern
             wf2crc0: RW uint = 0..=15,
         },
         /// Wakeup Frame 2 CRC1 Register
@@ -271,7 +271,7 @@ device_driver::create_device!(
             const ADDRESS = 0x52;
             const SIZE_BITS = 16;
             /// Wake up Frame 2 CRC (upper 16 bits)
-            /// The expected CRC value of a Wake up frame 0 pattern
+            /// The expected CRC value of a Wake up frame 2 pattern
             wf2crc1: RW uint = 0..=15,
         },
         /// Wakeup Frame 2 Byte Mask 0 Register
@@ -280,7 +280,7 @@ device_driver::create_device!(
             const SIZE_BITS = 16;
 
             /// Wake up Frame 2 Byte Mask 0
-            /// The first 16 bytes mask of a Wake up frame 0 pattern.
+            /// The first 16 bytes mask of a Wake up frame 2 pattern.
             wf2bm0: RW uint = 0..=15,
         },
         /// Wakeup Frame 2 Byte Mask 1 Register
@@ -289,7 +289,7 @@ device_driver::create_device!(
             const SIZE_BITS = 16;
 
             /// Wake up Frame 2 Byte Mask 1
-            /// The next 16 bytes mask covering bytes 17 to 32 of a Wake up frame 0 pattern.
+            /// The next 16 bytes mask covering bytes 17 to 32 of a Wake up frame 2 pattern.
             wf2bm1: RW uint = 0..=15,
         },
         /// Wakeup Frame 2 Byte Mask 2 Register
@@ -298,7 +298,7 @@ device_driver::create_device!(
             const SIZE_BITS = 16;
 
             /// Wake up Frame 2 Byte Mask 2
-            /// The next 16 bytes mask covering bytes 33 to 48 of a Wake-up frame 0 pattern.
+            /// The next 16 bytes mask covering bytes 33 to 48 of a Wake up frame 2 pattern.
             wf2bm2: RW uint = 0..=15,
         },
         /// Wakeup Frame 2 Byte Mask 3 Register
@@ -307,8 +307,8 @@ device_driver::create_device!(
             const SIZE_BITS = 16;
 
             /// Wake up Frame 2 Byte Mask 3
-            /// The last 16 bytes mask covering bytes 49 to 64 of a Wake-up frame 0 pattern.
-            wf2bm2: RW uint = 0..=15,
+            /// The last 16 bytes mask covering bytes 49 to 64 of a Wake up frame 2 pattern.
+            wf2bm3: RW uint = 0..=15,
         },
 
         // Wakeup frame 3
@@ -318,7 +318,7 @@ device_driver::create_device!(
             const ADDRESS = 0x60;
             const SIZE_BITS = 16;
             /// Wake up Frame 3 CRC (lower 16 bits)
-            /// The expected CRC value of a Wake up frame 0 pattern
+            /// The expected CRC value of a Wake up frame 3 pattern
             wf3crc0: RW uint = 0..=15,
         },
         /// Wakeup Frame 3 CRC1 Register
@@ -326,7 +326,7 @@ device_driver::create_device!(
             const ADDRESS = 0x62;
             const SIZE_BITS = 16;
             /// Wake up Frame 3 CRC (upper 16 bits)
-            /// The expected CRC value of a Wake up frame 0 pattern
+            /// The expected CRC value of a Wake up frame 3 pattern
             wf3crc1: RW uint = 0..=15,
         },
         /// Wakeup Frame 3 Byte Mask 0 Register
@@ -335,7 +335,7 @@ device_driver::create_device!(
             const SIZE_BITS = 16;
 
             /// Wake up Frame 3 Byte Mask 0
-            /// The first 16 bytes mask of a Wake up frame 0 pattern.
+            /// The first 16 bytes mask of a Wake up frame 3 pattern.
             wf3bm0: RW uint = 0..=15,
         },
         /// Wakeup Frame 3 Byte Mask 1 Register
@@ -344,7 +344,7 @@ device_driver::create_device!(
             const SIZE_BITS = 16;
 
             /// Wake up Frame 3 Byte Mask 1
-            /// The next 16 bytes mask covering bytes 17 to 32 of a Wake up frame 0 pattern.
+            /// The next 16 bytes mask covering bytes 17 to 32 of a Wake up frame 3 pattern.
             wf3bm1: RW uint = 0..=15,
         },
         /// Wakeup Frame 3 Byte Mask 2 Register
@@ -353,7 +353,7 @@ device_driver::create_device!(
             const SIZE_BITS = 16;
 
             /// Wake up Frame 3 Byte Mask 2
-            /// The next 16 bytes mask covering bytes 33 to 48 of a Wake-up frame 0 pattern.
+            /// The next 16 bytes mask covering bytes 33 to 48 of a Wake up frame 3 pattern.
             wf3bm2: RW uint = 0..=15,
         },
         /// Wakeup Frame 3 Byte Mask 3 Register
@@ -362,14 +362,15 @@ device_driver::create_device!(
             const SIZE_BITS = 16;
 
             /// Wake up Frame 3 Byte Mask 3
-            /// The last 16 bytes mask covering bytes 49 to 64 of a Wake-up frame 0 pattern.
-            wf3bm2: RW uint = 0..=15,
+            /// The last 16 bytes mask covering bytes 49 to 64 of a Wake up frame 3 pattern.
+            wf3bm3: RW uint = 0..=15,
         },
 
         /// Transmit Control Register
         register TXCR {
             const ADDRESS = 0x70;
             const SIZE_BITS = 16;
+            const RESET_VALUE = 0x0000;
 
             /// Transmit Checksum Generation for ICMP
             /// When this bit is set, The KSZ8851SNL is enabled to transmit ICMP frame (only
@@ -436,6 +437,7 @@ device_driver::create_device!(
         register RXCR1 {
             const ADDRESS = 0x74;
             const SIZE_BITS = 16;
+            const RESET_VALUE = 0x0800;
 
             /// Flush Receive Queue
             /// When this bit is set, The receive queue memory is cleared and RX frame
@@ -513,6 +515,7 @@ device_driver::create_device!(
         register RXCR2 {
             const ADDRESS = 0x76;
             const SIZE_BITS = 16;
+            const RESET_VALUE = 0x0004;
 
             /// SPI Receive Data Burst Length
             /// These three bits are used to define for SPI receive data burst length
@@ -566,6 +569,8 @@ device_driver::create_device!(
             const ADDRESS = 0x78;
             const SIZE_BITS = 16;
 
+            /// TXQ Memory Available
+            /// The amount of free TXQ memory, in bytes.
             txma: uint = 0..=12,
         },
 
@@ -677,6 +682,7 @@ device_driver::create_device!(
         register RXQCR {
             const ADDRESS = 0x82;
             const SIZE_BITS = 16;
+            const RESET_VALUE = 0x0000;
 
             /// RX Duration Timer Threshold Status
             rxdtts: bool = 12,
@@ -744,6 +750,7 @@ device_driver::create_device!(
         register IER {
             const ADDRESS = 0x90;
             const SIZE_BITS = 16;
+            const RESET_VALUE = 0x0000;
 
             /// Link Change Interrupt Enable
             lcie: RW bool = 15,
@@ -802,6 +809,8 @@ device_driver::create_device!(
             edis: RW bool = 2,
             /// SPI Bus Error Interrupt Status
             spibeis: RW bool = 1,
+            /// Delay Energy Detect Interrupt Status
+            dedis: RW bool = 0,
         },
 
         /// RX Frame Count & Threshold Register
@@ -881,7 +890,7 @@ device_driver::create_device!(
             const SIZE_BITS = 16;
 
             /// Flow Control Overrun Watermark Configuration
-            fclwc: RW uint = 0..=11,
+            fcowc: RW uint = 0..=11,
         },
 
         /// Chip ID and Enable Register
@@ -912,6 +921,65 @@ device_driver::create_device!(
 
         // TODO: some missing registers here
 
+        /// Power Management Event Control Register
+        register PMECR {
+            const ADDRESS = 0xD4;
+            const SIZE_BITS = 16;
+
+            /// PME Delay Enable
+            /// When set, asserting the PME output is delayed after a wake-up event.
+            pme_delay_enable: RW bool = 14,
+            /// PME Output Polarity
+            /// 1 = PME pin is active high, 0 = PME pin is active low.
+            pme_output_polarity: RW bool = 12,
+            /// Wake-up frame to PME output enable
+            wake_up_frame_pme_enable: RW bool = 11,
+            /// Magic packet to PME output enable
+            magic_packet_pme_enable: RW bool = 10,
+            /// Link change to PME output enable
+            link_change_pme_enable: RW bool = 9,
+            /// Energy detect to PME output enable
+            energy_detect_pme_enable: RW bool = 8,
+            /// Auto Wake-up Enable
+            /// When set, the chip returns from energy detect mode to normal mode when
+            /// energy is detected on the cable.
+            auto_wake_up_enable: RW bool = 7,
+            /// Wake-up to Normal Operation Mode
+            /// When set, the chip returns to normal mode on a wake-up event.
+            wake_up_to_normal_mode: RW bool = 6,
+            /// Wake-up frame event detected. Write 1 to clear.
+            wake_up_frame_event: RW bool = 5,
+            /// Magic packet event detected. Write 1 to clear.
+            magic_packet_event: RW bool = 4,
+            /// Link up event detected. Write 1 to clear.
+            link_up_event: RW bool = 3,
+            /// Energy event detected. Write 1 to clear.
+            energy_event: RW bool = 2,
+            /// Power Management Mode
+            power_management_mode: RW uint as enum PowerManagementMode {
+                Normal = 0,
+                EnergyDetect = 1,
+                SoftPowerDown = 2,
+                Reserved = 3,
+            } = 0..=1,
+        },
+
+        /// Go-sleep & Wake-up Time Register
+        /// Timing for the energy detect power management mode.
+        register GSWUTR {
+            const ADDRESS = 0xD6;
+            const SIZE_BITS = 16;
+
+            /// Wake-up Time
+            /// How long energy must be present on the cable before the chip wakes up
+            /// from energy detect sleep.
+            wake_up_time: RW uint = 8..=15,
+            /// Go-sleep Time
+            /// How long the cable must show no energy before the chip goes to sleep
+            /// in energy detect mode.
+            go_sleep_time: RW uint = 0..=7,
+        },
+
         /// PHY 1 MII-Register Basic Control Register
         register P1MBCR {
             const ADDRESS = 0xE4;
@@ -986,8 +1054,74 @@ device_driver::create_device!(
             extended_capable: bool = 0,
         },
 
+        /// PHY 1 PHY ID Low Register
+        /// Reads 0x1430 on the KSZ8851SNL
+        register PHY1ILR {
+            const ADDRESS = 0xE8;
+            const SIZE_BITS = 16;
+
+            /// PHY ID low word (MII register 3)
+            id_low: RO uint = 0..=15,
+        },
+
+        /// PHY 1 PHY ID High Register
+        /// Reads 0x0022 on the KSZ8851SNL
+        register PHY1IHR {
+            const ADDRESS = 0xEA;
+            const SIZE_BITS = 16;
+
+            /// PHY ID high word (MII register 2)
+            id_high: RO uint = 0..=15,
+        },
+
         // TODO: A few others here too
 
+        /// Port 1 Status Register
+        register P1SR {
+            const ADDRESS = 0xF8;
+            const SIZE_BITS = 16;
+
+            /// HP_mdix
+            /// 1 = HP Auto MDI-X mode.
+            /// 0 = Microchip Auto MDI-X mode.
+            hp_mdix: bool = 15,
+            /// Polarity Reverse
+            /// 1 = polarity is reversed.
+            /// 0 = polarity is not reversed.
+            polarity_reverse: bool = 13,
+            /// Operation Speed
+            /// 1 = link speed is 100 Mbps.
+            /// 0 = link speed is 10 Mbps.
+            operation_speed: bool = 10,
+            /// Operation Duplex
+            /// 1 = link duplex is full.
+            /// 0 = link duplex is half.
+            operation_duplex: bool = 9,
+            /// MDI status
+            /// 1 = MDI.
+            /// 0 = MDI-X.
+            mdi_status: bool = 7,
+            /// AN Done
+            /// 1 = auto-negotiation completed.
+            /// 0 = auto-negotiation not completed.
+            an_done: bool = 6,
+            /// Link Good
+            /// 1 = link good.
+            /// 0 = link not good.
+            link_good: bool = 5,
+            /// Partner Flow Control Capable
+            /// 1 = link partner flow control (pause) capable.
+            partner_flow_control_capable: bool = 4,
+            /// Partner 100BT Full-Duplex Capable
+            partner_x100_full_capable: bool = 3,
+            /// Partner 100BT Half-Duplex Capable
+            partner_x100_half_capable: bool = 2,
+            /// Partner 10BT Full-Duplex Capable
+            partner_x10_full_capable: bool = 1,
+            /// Partner 10BT Half-Duplex Capable
+            partner_x10_half_capable: bool = 0,
+        },
+
         /// TX Control Word - used during TX FIFO operations
         /// This is not actually a real register! Don't try to read or write. This is defined here because
         /// Device driver doesn't currently allow standalone fieldsets. See https://github.com/diondokter/device-driver/issues/77
@@ -1001,10 +1135,52 @@ device_driver::create_device!(
     }
 );
 
+impl SpiRxDataBurstLength {
+    /// The raw 3 bit `RXCR2::srdbl` value, including for [`SpiRxDataBurstLength::Reserved`].
+    pub fn raw_value(&self) -> u8 {
+        u8::from(*self)
+    }
+
+    /// The number of bytes per burst, or `None` for single frame bursts and reserved values.
+    pub fn burst_size_bytes(&self) -> Option<u16> {
+        match self {
+            Self::X4Bytes => Some(4),
+            Self::X8Bytes => Some(8),
+            Self::X16Bytes => Some(16),
+            Self::X32Bytes => Some(32),
+            Self::SingleFrame | Self::Reserved(_) => None,
+        }
+    }
+
+    /// The burst length for `bytes` (4, 8, 16 or 32) bytes per burst, the inverse of
+    /// [`SpiRxDataBurstLength::burst_size_bytes`].
+    pub fn from_burst_size_bytes(bytes: u16) -> Option<Self> {
+        match bytes {
+            4 => Some(Self::X4Bytes),
+            8 => Some(Self::X8Bytes),
+            16 => Some(Self::X16Bytes),
+            32 => Some(Self::X32Bytes),
+            _ => None,
+        }
+    }
+}
+
+/// Register access over SPI.
+///
+/// Register data is always little endian on the bus: the SNL selects the bytes of a
+/// register with the byte enables in the command, and has no byte order setting (the
+/// `CGCR` endian bits only exist on the parallel bus parts).
 pub struct Ksz8851snlInterface<BUS> {
     pub bus: BUS,
 }
 
+impl<BUS> Ksz8851snlInterface<BUS> {
+    /// Unwrap the SPI device.
+    pub fn into_inner(self) -> BUS {
+        self.bus
+    }
+}
+
 impl<BUS: embedded_hal_async::spi::SpiDevice> device_driver::AsyncRegisterInterface
     for Ksz8851snlInterface<BUS>
 {
@@ -1025,6 +1201,7 @@ impl<BUS: embedded_hal_async::spi::SpiDevice> device_driver::AsyncRegisterInterf
                 Operation::Read(data),
             ])
             .await?;
+        driver_trace!("read {=u8:#04x} = {=[u8]:#04x}", address, data);
         Ok(())
     }
 
@@ -1035,6 +1212,7 @@ impl<BUS: embedded_hal_async::spi::SpiDevice> device_driver::AsyncRegisterInterf
         data: &[u8],
     ) -> Result<(), Self::Error> {
         assert!(size_bits == 16);
+        driver_trace!("write {=u8:#04x} = {=[u8]:#04x}", address, data);
         self.bus
             .transaction(&mut [
                 Operation::Write(&reg_cmd(Opcode::RegWrite, address, 2)),
@@ -1043,3 +1221,85 @@ impl<BUS: embedded_hal_async::spi::SpiDevice> device_driver::AsyncRegisterInterf
             .await
     }
 }
+
+impl<BUS: embedded_hal::spi::SpiDevice> device_driver::RegisterInterface
+    for Ksz8851snlInterface<BUS>
+{
+    type Error = BUS::Error;
+
+    type AddressType = u8;
+
+    fn read_register(
+        &mut self,
+        address: Self::AddressType,
+        size_bits: u32,
+        data: &mut [u8],
+    ) -> Result<(), Self::Error> {
+        assert!(size_bits == 16);
+        self.bus.transaction(&mut [
+            Operation::Write(&reg_cmd(Opcode::RegRead, address, 2)),
+            Operation::Read(data),
+        ])?;
+        driver_trace!("read {=u8:#04x} = {=[u8]:#04x}", address, data);
+        Ok(())
+    }
+
+    fn write_register(
+        &mut self,
+        address: Self::AddressType,
+        size_bits: u32,
+        data: &[u8],
+    ) -> Result<(), Self::Error> {
+        assert!(size_bits == 16);
+        driver_trace!("write {=u8:#04x} = {=[u8]:#04x}", address, data);
+        self.bus.transaction(&mut [
+            Operation::Write(&reg_cmd(Opcode::RegWrite, address, 2)),
+            Operation::Write(data),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{MockSpi, block_on};
+    use device_driver::{AsyncRegisterInterface, RegisterInterface};
+
+    #[test]
+    fn write_register() {
+        let mut interface = Ksz8851snlInterface {
+            bus: MockSpi::new(),
+        };
+        // TXCR, and RXCR2 in the upper half of its 4 byte word
+        block_on(AsyncRegisterInterface::write_register(
+            &mut interface,
+            0x70,
+            16,
+            &[0x34, 0x12],
+        ))
+        .unwrap();
+        RegisterInterface::write_register(&mut interface, 0x76, 16, &[0x78, 0x56]).unwrap();
+        assert_eq!(interface.bus.writes, [(0x70, 0x1234), (0x76, 0x5678)]);
+
+        let mut data = [0u8; 2];
+        block_on(AsyncRegisterInterface::read_register(
+            &mut interface,
+            0x76,
+            16,
+            &mut data,
+        ))
+        .unwrap();
+        assert_eq!(data, [0x78, 0x56]);
+        RegisterInterface::read_register(&mut interface, 0x70, 16, &mut data).unwrap();
+        assert_eq!(data, [0x34, 0x12]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn write_register_not_16_bits() {
+        let mut interface = Ksz8851snlInterface {
+            bus: MockSpi::new(),
+        };
+        RegisterInterface::write_register(&mut interface, 0x70, 8, &[0x34]).unwrap();
+    }
+}
@@ -1,424 +1,3864 @@
-use crate::device::field_sets::{Rxfhbcr, Rxfhsr, TxCtrlWord};
-use crate::device::{Ksz8851snl, Ksz8851snlInterface, SpiRxDataBurstLength};
-use device_driver::FieldSet;
-use embedded_hal::spi::{self, ErrorKind};
+//! The driver: [`Chip`] and its configuration and status types.
+//!
+//! # SPI protocol
+//! Every transaction starts with an opcode in the top two bits of the first byte (see
+//! [`crate::raw`]), with the chip select held for the whole transaction.
+//!
+//! Register read/write. Registers are accessed 16 bits at a time, selected with the byte
+//! enables within the 4-aligned address, and data is little endian:
+//! ```text
+//!   byte 0                       byte 1                  byte 2    byte 3
+//!   +----+---------+-------+     +-------+---------+     +---------+---------+
+//!   | op | BE[3:0] | A7:A6 |     | A5:A2 | 0000    |     | data lo | data hi |
+//!   | 2  | 4       | 2     |     | 4     | 4       |     | 8       | 8       |
+//!   +----+---------+-------+     +-------+---------+     +---------+---------+
+//!   op 00 = read, 01 = write; BE 0011 = low half, 1100 = high half (A1 set)
+//! ```
+//!
+//! TXQ FIFO write (op 11), with `RXQCR::sda` set around it:
+//! ```text
+//!   +--------+--------------+------------+---------//--------+-----+
+//!   | opcode | control word | byte count | frame data        | pad |
+//!   | 1 byte | 2            | 2          | n (no CRC)        | 0-3 |
+//!   +--------+--------------+------------+---------//--------+-----+
+//!   control word: TxIC (bit 15) | frame ID (6 bits); byte count = n
+//!   the pad makes everything after the opcode a multiple of 4 bytes, and isn't sent
+//! ```
+//!
+//! RXQ FIFO read (op 10), with `RXQCR::sda` set around it and the frame pointer reset:
+//! ```text
+//!   +--------+---------+--------+---------+------------+------//-----+-----+-----+
+//!   | opcode | dummy   | RXFHSR | RXFHBCR | IP hdr pad | frame data  | CRC | pad |
+//!   | 1 byte | 4       | 2      | 2       | 0 or 2     | n           | 4   | 0-3 |
+//!   +--------+---------+--------+---------+------------+------//-----+-----+-----+
+//!                                         \_____________ byte count ____________/
+//!   the IP header pad is only present with RXQCR::rxiphtoe set
+//! ```
+use crate::device::field_sets::{
+    Cider, Ier, Isr, Marh, Marl, Marm, Mbir, P1Sr, Rxcr1, Rxcr2, Rxfhbcr, Rxfhsr, Rxqcr,
+    TxCtrlWord, Txcr,
+};
+use crate::device::{
+    Ksz8851snl, Ksz8851snlInterface, Ocbcds, PowerManagementMode, SpiRxDataBurstLength,
+};
+use crate::raw::Opcode;
+use core::marker::PhantomData;
+use device_driver::{AsyncRegisterInterface, FieldSet};
+use embedded_hal::delay::DelayNs as BlockingDelayNs;
+use embedded_hal::spi::{self, ErrorKind, SpiDevice as BlockingSpiDevice};
 use embedded_hal_async::delay::DelayNs;
+use embedded_hal_async::digital::Wait;
 use embedded_hal_async::spi::{Operation, SpiDevice};
 
-#[repr(u8)]
-pub(crate) enum Opcode {
-    RegRead = 0b00,
-    RegWrite = 0b01,
-    RXRead = 0b10,
-    TXWrite = 0b11,
+const CHIP_ID_FAMILY: u8 = 0x88;
+const CHIP_ID_CHIP: u8 = 0x7;
+/// How long to wait for auto-negotiation to complete. AN normally takes well under
+/// a second, but can take longer with parallel detection or a slow link partner.
+const AN_TIMEOUT_MS: u32 = 5000;
+/// How long [`Chip::init`] waits for the memory BIST to finish after reset. It normally
+/// takes a few hundred microseconds.
+const BIST_TIMEOUT_MS: u32 = 100;
+/// How long TX waits for a frame to be sent, when checking it for collisions or before
+/// enqueueing the next one. A maximum size frame that collides 16 times at 10 Mbps can
+/// take a few hundred ms with backoff.
+const TX_COMPLETE_TIMEOUT_MS: u32 = 1000;
+/// How long [`Chip::configure_local_loopback_test`] waits for the loopback "link", and
+/// [`Chip::loopback_tx_rx_test`] for the frame to come back.
+const LOOPBACK_TIMEOUT_MS: u32 = 100;
+/// How long [`Chip::rx_discard_frame`] waits for the chip to release a frame by default,
+/// see [`Chip::configure_rx_discard_timeout`].
+const RX_DISCARD_TIMEOUT_US: u32 = 1000;
+/// The largest frame ID that fits `TxCtrlWord::frame_id` (6 bits). `build.rs` checks the
+/// field is still that wide.
+const MAX_FRAME_ID: u8 = 0x3f;
+/// The largest frame the KSZ8851SNL can send or receive, in bytes. This is more than the
+/// 1518 (or 1522 with a VLAN tag) that standard Ethernet allows.
+pub const MAX_FRAME_SIZE: usize = 2000;
+/// The largest byte count (`RXFHBCR::rxbc`) a received frame can have: [`MAX_FRAME_SIZE`]
+/// plus the CRC and the IP header pad (see [`Chip::configure_ip_header_offset_words`]).
+/// Anything bigger can only come from a corrupted read.
+pub const MAX_RX_FRAME_BYTES: u16 = MAX_FRAME_SIZE as u16 + 4 + 2;
+/// The size of the RXQ frame buffer, in bytes.
+pub const RX_BUFFER_SIZE: u16 = 12 * 1024;
+/// The largest value the 12 bit flow control watermark registers can hold.
+const MAX_WATERMARK: u16 = 0xfff;
+// Frame IDs wrap with `& MAX_FRAME_ID`
+const _: () = assert!((MAX_FRAME_ID as u16 + 1).is_power_of_two());
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Error {
+    SpiError(ErrorKind),
+    BadChipId {
+        expected_family: u8,
+        actual_family: u8,
+        expected_chip: u8,
+        actual_chip: u8,
+    },
+    FailedBuiltInSelfTest {
+        rx_bist_failed: bool,
+        tx_bist_failed: bool,
+    },
+    /// A TX frame is longer than `max` ([`MAX_FRAME_SIZE`])
+    TxPacketTooBig {
+        size: usize,
+        max: u16,
+    },
+    RxFrameInvalid,
+    /// The frame at the head of the RXQ doesn't fit in the buffer given. It is left in the
+    /// RXQ, to be read into a bigger buffer or dropped with [`Chip::rx_discard_frame`].
+    RxBufferTooSmall {
+        needed: usize,
+        provided: usize,
+    },
+    /// The chip reported a received frame bigger than it can receive
+    /// ([`MAX_RX_FRAME_BYTES`]), or the frame header in the RXQ didn't match `RXFHSR` and
+    /// `RXFHBCR`. Both point to SPI corruption. The frame is dropped.
+    RxFrameCorrupt {
+        byte_count: u16,
+    },
+    /// There is no complete frame at the head of the RXQ (`RXFHSR::rxfv` is clear).
+    ///
+    /// `frame_count` (`RXFCTR::rxfc`) and `byte_count` (`RXFHBCR::rxbc`) are what the chip
+    /// reported at the time. Both are 0 when there really is nothing waiting; a non-zero
+    /// `frame_count` with no valid frame means the RXQ state is inconsistent, which
+    /// [`Chip::drain_rx_queue`] recovers from.
+    RxNoFrameAvailable {
+        frame_count: u8,
+        byte_count: u16,
+    },
+    /// Flow control was enabled on a half-duplex link, see [`Chip::configure_flow_control`]
+    FlowControlRequiresFullDuplex,
+    RegisterDefaultMismatch {
+        register: &'static str,
+        expected: u16,
+        actual: u16,
+    },
+    InvalidFrameId {
+        frame_id: u8,
+    },
+    AutoNegotiationTimeout,
+    TxCompleteTimeout,
+    /// A TXQ/RXQ DMA transfer was started while another was still open, e.g. because a
+    /// previous `tx()`/`rx()` future was dropped part way through. [`Chip::reset`] recovers.
+    DmaConflict,
+    /// [`Chip::write_tx_payload`] was called without a preceding
+    /// [`Chip::write_tx_register_header`].
+    NoTxInProgress,
+    /// The payload passed to [`Chip::write_tx_payload`] isn't the length written in the header.
+    TxLengthMismatch {
+        expected: usize,
+        actual: usize,
+    },
+    /// The chip reported a different frame as sent (`TXSR::txfid`) than the last one written
+    TxFrameIdMismatch {
+        sent: u8,
+        confirmed: u8,
+    },
+    /// There wasn't room in the TXQ in time, see [`Chip::tx_with_timeout`]
+    TxTimeout {
+        bytes_needed: usize,
+        bytes_available: u16,
+    },
+    /// A transmitted frame was dropped because of a collision, see
+    /// [`Chip::configure_collision_handling`]
+    TxCollision,
+    /// There are only wake-up frame slots 0-3, see [`Chip::configure_wakeup_frame`]
+    InvalidWakeupFrameSlot {
+        slot: u8,
+    },
+    /// A register address that isn't 2-aligned, so can't be accessed
+    InvalidRegisterAddress {
+        address: u8,
+    },
+    /// The MAC address read back after [`Chip::set_mac`] wasn't the one written, see
+    /// [`InitConfig::verify_mac_writes`]
+    MacVerifyFailed,
+    /// The MAC address is all zeros, see [`Chip::configure_rx_source_address_filter`]
+    MacNotConfigured,
+    /// The KSZ8851SNL hardware doesn't have this feature
+    FeatureNotSupported,
+    /// The memory BIST didn't finish (`MBIR::txmbf`/`MBIR::rxmbf`) in time
+    BistTimeout,
+    /// The PHY didn't report a link in local loopback, see
+    /// [`Chip::configure_local_loopback_test`]
+    LoopbackLinkTimeout,
+    /// The chip didn't release a discarded RX frame (`RXQCR::rrxef`) in time, see
+    /// [`Chip::configure_rx_discard_timeout`].
+    RxDiscardTimeout,
+    /// The previous manually enqueued frame still hadn't been sent, see
+    /// [`Chip::tx_single_frame_guarantee`]
+    TxAlreadyInProgress,
+    /// The driver's RX functions need single frame RXQ bursts, see
+    /// [`Chip::configure_srdbl_burst_length_from_mtu`]
+    RxBurstLengthNotSingleFrame,
+    /// Flow control watermarks that are out of order or too big, see
+    /// [`Chip::configure_rx_overflow_threshold`]
+    InvalidThreshold,
+    /// A value the datasheet marks as reserved was passed for a register field
+    ReservedFieldValue {
+        register: &'static str,
+        field: &'static str,
+    },
+    /// A frame was sent or received before [`Chip::init`] (or after a [`Chip::reset`])
+    NotInitialized,
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::SpiError(kind) => write!(f, "SPI error: {kind}"),
+            Self::BadChipId {
+                expected_family,
+                actual_family,
+                expected_chip,
+                actual_chip,
+            } => write!(
+                f,
+                "bad chip ID: expected family {expected_family:#x} chip {expected_chip:#x}, \
+                 got family {actual_family:#x} chip {actual_chip:#x}"
+            ),
+            Self::FailedBuiltInSelfTest {
+                rx_bist_failed,
+                tx_bist_failed,
+            } => write!(
+                f,
+                "memory BIST failed (RX failed: {rx_bist_failed}, TX failed: {tx_bist_failed})"
+            ),
+            Self::TxPacketTooBig { size, max } => {
+                write!(f, "TX frame of {size} bytes is over the maximum of {max}")
+            }
+            Self::RxFrameInvalid => f.write_str("received frame was invalid and was dropped"),
+            Self::RxBufferTooSmall { needed, provided } => write!(
+                f,
+                "received frame needs a {needed} byte buffer, only {provided} given"
+            ),
+            Self::RxFrameCorrupt { byte_count } => {
+                write!(
+                    f,
+                    "received frame ({byte_count} bytes) was corrupted on SPI"
+                )
+            }
+            Self::RxNoFrameAvailable {
+                frame_count,
+                byte_count,
+            } => write!(
+                f,
+                "no received frame available ({frame_count} frames, {byte_count} bytes reported)"
+            ),
+            Self::FlowControlRequiresFullDuplex => {
+                f.write_str("flow control needs a full-duplex link")
+            }
+            Self::RegisterDefaultMismatch {
+                register,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "{register} reads {actual:#06x} after reset, expected {expected:#06x}"
+            ),
+            Self::InvalidFrameId { frame_id } => {
+                write!(f, "TX frame ID {frame_id:#x} doesn't fit in 6 bits")
+            }
+            Self::AutoNegotiationTimeout => f.write_str("auto-negotiation timed out"),
+            Self::TxCompleteTimeout => f.write_str("timed out waiting for TX to complete"),
+            Self::DmaConflict => f.write_str("a queue DMA transfer is already in progress"),
+            Self::NoTxInProgress => f.write_str("TX payload written without a header"),
+            Self::TxLengthMismatch { expected, actual } => write!(
+                f,
+                "TX payload is {actual} bytes, but the header said {expected}"
+            ),
+            Self::TxFrameIdMismatch { sent, confirmed } => write!(
+                f,
+                "sent TX frame ID {sent:#x}, but the chip reported {confirmed:#x}"
+            ),
+            Self::InvalidRegisterAddress { address } => {
+                write!(f, "register address {address:#04x} isn't 2-aligned")
+            }
+            Self::TxTimeout {
+                bytes_needed,
+                bytes_available,
+            } => write!(
+                f,
+                "timed out waiting for {bytes_needed} bytes of TXQ space, {bytes_available} free"
+            ),
+            Self::TxCollision => f.write_str("TX frame dropped after a collision"),
+            Self::InvalidWakeupFrameSlot { slot } => {
+                write!(f, "wake-up frame slot {slot} doesn't exist, there are 4")
+            }
+            Self::MacVerifyFailed => f.write_str("MAC address didn't read back as written"),
+            Self::MacNotConfigured => f.write_str("no MAC address has been set"),
+            Self::FeatureNotSupported => f.write_str("not supported by the KSZ8851SNL"),
+            Self::BistTimeout => f.write_str("timed out waiting for the memory BIST"),
+            Self::LoopbackLinkTimeout => f.write_str("no link in PHY loopback"),
+            Self::TxAlreadyInProgress => f.write_str("the previous TX frame is still queued"),
+            Self::RxDiscardTimeout => {
+                f.write_str("timed out waiting for a discarded RX frame to be released")
+            }
+            Self::RxBurstLengthNotSingleFrame => {
+                f.write_str("RX needs RXCR2::srdbl set to single frame bursts")
+            }
+            Self::InvalidThreshold => f.write_str("invalid flow control watermarks"),
+            Self::ReservedFieldValue { register, field } => {
+                write!(f, "reserved value for {register}::{field}")
+            }
+            Self::NotInitialized => f.write_str("the chip hasn't been initialized"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl core::error::Error for Error {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        // `ErrorKind` isn't an `Error` itself
+        None
+    }
+}
+
+impl<SE: spi::Error> From<SE> for Error {
+    fn from(value: SE) -> Self {
+        Self::SpiError(value.kind())
+    }
+}
+
+/// How long the chip waits before acting on a change in cable energy, when energy
+/// detection is enabled with [`Chip::configure_energy_detect`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum EnergyDetectDelay {
+    /// Leave `GSWUTR` at its reset value.
+    Default,
+    /// Program the raw `GSWUTR::go_sleep_time` and `GSWUTR::wake_up_time` values. Longer
+    /// times filter out noise on the line at the cost of slower reaction to a real cable
+    /// being plugged in or removed.
+    Custom { go_sleep_time: u8, wake_up_time: u8 },
+}
+
+/// Interrupt events, as decoded by [`Chip::handle_interrupt`].
+///
+/// As an [`InterruptMask`], the same fields select which events raise an interrupt.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct InterruptStatus {
+    pub link_changed: bool,
+    pub tx_complete: bool,
+    pub rx_ready: bool,
+    pub rx_overrun: bool,
+    pub tx_stopped: bool,
+    pub rx_stopped: bool,
+    pub tx_space_available: bool,
+    pub wakeup_frame_detected: bool,
+    pub magic_packet_detected: bool,
+    pub linkup_detected: bool,
+    pub energy_detected: bool,
+    pub delayed_energy_detected: bool,
+    pub spi_bus_error: bool,
+}
+
+/// The interrupts to enable (`IER`), see [`InitConfig::interrupts`]. Each field enables
+/// the event of the same name.
+pub type InterruptMask = InterruptStatus;
+
+impl From<InterruptMask> for Ier {
+    fn from(mask: InterruptMask) -> Self {
+        let mut ier = Ier::new_zero();
+        ier.set_lcie(mask.link_changed);
+        ier.set_txie(mask.tx_complete);
+        ier.set_rxie(mask.rx_ready);
+        ier.set_rxoie(mask.rx_overrun);
+        ier.set_txpsie(mask.tx_stopped);
+        ier.set_rxpsie(mask.rx_stopped);
+        ier.set_txsaie(mask.tx_space_available);
+        ier.set_rxwfdie(mask.wakeup_frame_detected);
+        ier.set_rxmpdie(mask.magic_packet_detected);
+        ier.set_ldie(mask.linkup_detected);
+        ier.set_edie(mask.energy_detected);
+        ier.set_dedie(mask.delayed_energy_detected);
+        ier.set_spibeie(mask.spi_bus_error);
+        ier
+    }
+}
+
+impl From<Isr> for InterruptStatus {
+    fn from(isr: Isr) -> Self {
+        Self {
+            link_changed: isr.lcis(),
+            tx_complete: isr.txis(),
+            rx_ready: isr.rxis(),
+            rx_overrun: isr.rxois(),
+            tx_stopped: isr.txpsis(),
+            rx_stopped: isr.rxpsis(),
+            tx_space_available: isr.txsais(),
+            wakeup_frame_detected: isr.rxwfdis(),
+            magic_packet_detected: isr.rxmpdis(),
+            linkup_detected: isr.ldis(),
+            energy_detected: isr.edis(),
+            delayed_energy_detected: isr.dedis(),
+            spi_bus_error: isr.spibeis(),
+        }
+    }
+}
+
+/// Information about a received frame, from its `RXFHSR` status word.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RxFrameInfo {
+    /// Length of the frame, excluding the CRC
+    pub len: usize,
+    pub broadcast: bool,
+    /// Note this is also set for broadcast frames
+    pub multicast: bool,
+    pub unicast: bool,
+    /// Which checksums the chip verified, see [`RxFrameMetrics`]
+    pub checksums: RxFrameMetrics,
+}
+
+impl RxFrameInfo {
+    fn new(frame_len: u16, frame_status: Rxfhsr, checksums: RxFrameMetrics) -> Self {
+        Self {
+            len: frame_len.into(),
+            broadcast: frame_status.rxbf(),
+            multicast: frame_status.rxmf(),
+            unicast: frame_status.rxuf(),
+            checksums,
+        }
+    }
+}
+
+/// Which checksums of a received frame the chip has already verified, so a network stack
+/// can skip checking them again in software.
+///
+/// A field is set when hardware checking of that checksum is enabled (`RXCR1::rxipfcc`,
+/// `RXCR1::rxtcpfcc`, `RXCR1::rxudpfcc`, `RXCR2::rxicmpfcc`). Frames failing an enabled
+/// check are never returned by [`Chip::rx`], so for the frames that are, every enabled
+/// check has passed. It says nothing about whether the frame actually is e.g. TCP.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RxFrameMetrics {
+    pub ip_checksum_verified: bool,
+    pub tcp_checksum_verified: bool,
+    pub udp_checksum_verified: bool,
+    pub icmp_checksum_verified: bool,
+}
+
+/// Chip identification, from `CIDER`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ChipInfo {
+    pub family_id: u8,
+    pub chip_id: u8,
+    pub revision_id: u8,
+}
+
+/// An Ethernet MAC address, most significant byte first as usually written.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct MacAddress(pub [u8; 6]);
+
+impl From<[u8; 6]> for MacAddress {
+    fn from(value: [u8; 6]) -> Self {
+        Self(value)
+    }
+}
+
+impl From<MacAddress> for [u8; 6] {
+    fn from(value: MacAddress) -> Self {
+        value.0
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum LinkSpeed {
+    Mbps10,
+    Mbps100,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Duplex {
+    Half,
+    Full,
+}
+
+fn link_mode(p1sr: P1Sr) -> (LinkSpeed, Duplex) {
+    let speed = if p1sr.operation_speed() {
+        LinkSpeed::Mbps100
+    } else {
+        LinkSpeed::Mbps10
+    };
+    let duplex = if p1sr.operation_duplex() {
+        Duplex::Full
+    } else {
+        Duplex::Half
+    };
+    (speed, duplex)
+}
+
+/// Where the chip is in bringing up a connection, as driven by [`Chip::advance`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ConnectionState {
+    /// [`Chip::init`] hasn't been run (or the chip has been reset since)
+    Uninitialized,
+    /// Initialized, but there is no link
+    Disconnected,
+    /// There is a link, and we're waiting for auto-negotiation to complete
+    Connecting {
+        timeout_remaining: u32,
+    },
+    Connected {
+        speed: LinkSpeed,
+        duplex: Duplex,
+    },
+}
+
+/// Which frames the receiver accepts, from the `RXCR1` address filtering bits.
+///
+/// | Field       | Bit            | Accepts                                                |
+/// |-------------|----------------|--------------------------------------------------------|
+/// | `broadcast` | `RXCR1::rxbe`  | All broadcast frames. Needed for ARP!                  |
+/// | `multicast` | `RXCR1::rxme`  | All multicast frames (including broadcast)             |
+/// | `unicast`   | `RXCR1::rxue`  | Unicast frames to our MAC address                      |
+/// | `all`       | `RXCR1::rxae`  | Every frame, regardless of destination (promiscuous)   |
+///
+/// These combine with `RXCR1::rxpafma`, `RXCR1::rxmafma` (filter unicast/multicast
+/// through the MAC address and multicast hash table) and `RXCR1::rxinvf` (inverse
+/// filtering), which are left at their reset values (or as set by
+/// [`Chip::configure_mac_filter`]); see the Address Filtering Scheme table in the
+/// datasheet for every combination.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RxFilterConfig {
+    pub broadcast: bool,
+    pub multicast: bool,
+    pub unicast: bool,
+    pub all: bool,
+}
+
+impl RxFilterConfig {
+    /// Broadcast and unicast, as set up by [`Chip::init`].
+    pub const fn new() -> Self {
+        Self {
+            broadcast: true,
+            multicast: false,
+            unicast: true,
+            all: false,
+        }
+    }
+}
+
+impl RxFilterConfig {
+    fn apply(&self, r: &mut Rxcr1) {
+        r.set_rxbe(self.broadcast);
+        r.set_rxme(self.multicast);
+        r.set_rxue(self.unicast);
+        r.set_rxae(self.all);
+    }
+}
+
+impl Default for RxFilterConfig {
+    /// See [`RxFilterConfig::new`]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Unicast address filtering, see [`Chip::configure_mac_filter`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum UnicastFilter {
+    /// Don't receive unicast frames
+    Disabled,
+    /// Receive unicast frames that exactly match our MAC address
+    Perfect,
+}
+
+/// Multicast address filtering, see [`Chip::configure_mac_filter`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum MulticastFilter {
+    /// Don't receive multicast frames
+    Disabled,
+    /// Receive multicast frames whose address hashes to a bit set in the multicast hash
+    /// table (`MAHTR0`-`MAHTR3`)
+    HashFilter,
+    /// Receive every multicast frame
+    AllMulticast,
 }
 
-const CHIP_ID_FAMILY: u8 = 0x88;
-const CHIP_ID_CHIP: u8 = 0x7;
+/// The outcome of [`Chip::test_spi_loopback`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SpiLoopbackResult {
+    pub write_value: u16,
+    pub read_value: u16,
+    /// `read_value == write_value`
+    pub passed: bool,
+}
+
+/// Read/write registers exercised by [`Chip::run_register_loopback_tests`]: name,
+/// address and the mask of their read/write bits.
+const LOOPBACK_TEST_REGISTERS: [(&str, u8, u16); 10] = [
+    ("MARL", 0x10, 0xffff),
+    ("MARM", 0x12, 0xffff),
+    ("MARH", 0x14, 0xffff),
+    ("MAHTR0", 0xa0, 0xffff),
+    ("MAHTR1", 0xa2, 0xffff),
+    ("MAHTR2", 0xa4, 0xffff),
+    ("MAHTR3", 0xa6, 0xffff),
+    ("FCLWR", 0xb0, 0x0fff),
+    ("FCHWR", 0xb2, 0x0fff),
+    ("FCOWR", 0xb4, 0x0fff),
+];
+
+/// The patterns written by [`Chip::run_register_loopback_tests`], between them setting
+/// and clearing every bit.
+const LOOPBACK_TEST_PATTERNS: [u16; 2] = [0x5aa5, 0xa55a];
+
+/// The result of testing one register in [`Chip::run_register_loopback_tests`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RegisterTestResult {
+    pub register: &'static str,
+    pub address: u8,
+    pub passed: bool,
+}
+
+/// The results of [`Chip::run_register_loopback_tests`], one per register tested.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RegisterTestResults {
+    pub results: [RegisterTestResult; LOOPBACK_TEST_REGISTERS.len()],
+}
+
+impl RegisterTestResults {
+    /// Did every register pass?
+    pub fn all_passed(&self) -> bool {
+        self.results.iter().all(|r| r.passed)
+    }
+}
+
+/// TX queue priority, see [`Chip::set_tx_priority`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum QosPriority {
+    Low,
+    Medium,
+    High,
+}
+
+/// What to do about TX collisions, see [`Chip::configure_collision_handling`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CollisionMode {
+    /// Drop the frame silently
+    #[default]
+    Discard,
+    /// Send the frame again, up to `max_retries` times, then return [`Error::TxCollision`]
+    Retry { max_retries: u8 },
+    /// Return [`Error::TxCollision`]
+    RaiseError,
+}
+
+/// A wake-up frame pattern, see [`Chip::configure_wakeup_frame`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct WakeupPattern {
+    /// The first 64 bytes of a matching frame
+    pub pattern: [u8; 64],
+    /// Bit `n` set means byte `n` of `pattern` is compared
+    pub mask: u64,
+}
+
+/// A wake-up frame slot as programmed in the chip, see [`Chip::read_wakeup_frame_config`].
+///
+/// The chip only stores the CRC of the masked pattern bytes, not the pattern itself.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct WakeupFramePattern {
+    /// `WFxCRC0`/`WFxCRC1`
+    pub crc: u32,
+    /// `WFxBM0`-`WFxBM3`, bit `n` covering byte `n`
+    pub mask: u64,
+    /// `WFCR::wfxe`
+    pub enabled: bool,
+}
+
+/// MDI/MDI-X selection, see [`Chip::configure_mdix`].
+///
+/// | Mode         | `P1MBCR::disable_mdix` | `P1MBCR::force_mdix` | `P1MBCR::hp_mdix` |
+/// |--------------|------------------------|----------------------|-------------------|
+/// | `Auto`       | 0                      | 0                    | 0                 |
+/// | `AutoHp`     | 0                      | 0                    | 1                 |
+/// | `ManualMdi`  | 1                      | 0                    | 0                 |
+/// | `ManualMdix` | 1                      | 1                    | 0                 |
+///
+/// `force_mdix` only means anything with auto MDI-X disabled, and `hp_mdix` with it
+/// enabled.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum MdixMode {
+    /// Auto MDI-X, using Microchip's detection algorithm
+    Auto,
+    /// Auto MDI-X, using HP's detection algorithm. Some switches (e.g. HP/Aruba) only link
+    /// reliably with this one.
+    AutoHp,
+    /// Always MDI (straight through)
+    ManualMdi,
+    /// Always MDI-X (crossover)
+    ManualMdix,
+}
+
+/// What woke the chip up, see [`Chip::wait_wakeup_event`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum WakeupEvent {
+    /// A magic packet for our MAC address (`ISR::rxmpdis`)
+    MagicPacket,
+    /// A wake-up frame matched (`ISR::rxwfdis`). The chip doesn't report which slot.
+    WakeupFrame,
+    /// Energy on the cable (`ISR::edis`)
+    EnergyDetect,
+}
+
+/// What should wake the chip (and host) up, see [`Chip::enable_wakeup_and_sleep`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct WakeupConfig {
+    /// Wake on a magic packet for our MAC address (`WFCR::mprxe`)
+    pub magic_packet: bool,
+    /// Wake-up frame slots 0-3. `None` disables the slot.
+    pub patterns: [Option<WakeupPattern>; 4],
+    /// Power the PHY down while there is no energy on the cable, and wake when there is
+    pub energy_detect: bool,
+}
+
+/// The state of the last transmitted frame, see [`Chip::poll_tx_complete`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TxCompletionStatus {
+    /// The frame is still waiting to be sent. The other fields are then from the frame
+    /// before it.
+    pub in_progress: bool,
+    /// Late collision (`TXSR::txlc`)
+    pub late_collision: bool,
+    /// Maximum collisions (`TXSR::txmc`), the frame was dropped after 16 attempts
+    pub max_collision: bool,
+    /// The frame ID the status belongs to (`TXSR::txfid`)
+    pub frame_id: u8,
+}
+
+/// Configuration applied by [`Chip::init_with_config`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct InitConfig {
+    pub rx_filter: RxFilterConfig,
+    /// See [`Chip::set_tx_auto_pad`]
+    pub tx_auto_pad: bool,
+    /// See [`Chip::set_tx_auto_crc`]
+    pub tx_auto_crc: bool,
+    /// Enable flow control (see [`Chip::configure_flow_control`]) whenever
+    /// [`Chip::advance`] sees a full-duplex link come up. On by default.
+    pub flow_control: bool,
+    /// See [`Chip::configure_rx_source_address_filter`]. The filter compares against our
+    /// MAC address, so it should only be on once a valid one is set: `init` only enables
+    /// it if the chip already has a (non-zero) MAC address, e.g. loaded from EEPROM.
+    pub rx_source_address_filter: bool,
+    /// Read the MAC address back after every [`Chip::set_mac`] (see
+    /// [`Chip::verify_mac_address`]). Off by default.
+    pub verify_mac_writes: bool,
+    /// Drop received frames with a bad IP, TCP or UDP checksum (`RXCR1::rxipfcc`,
+    /// `rxtcpfcc`, `rxudpfcc`), see [`RxFrameMetrics`]. Off by default. ICMP checksums are
+    /// verified either way, see [`Chip::configure_icmp_checksum_offload`].
+    pub rx_checksum_verification: bool,
+    /// Fill in the IP and TCP checksums of transmitted frames (`TXCR::tcgip`, `tcgtcp`).
+    /// Off by default. The chip can't generate UDP checksums.
+    pub tx_checksum_generation: bool,
+    /// Have the chip insert a 2 byte pad before each received frame (`RXQCR::rxiphtoe`), see
+    /// [`Chip::configure_ip_header_offset_words`]. On by default.
+    pub rx_ip_header_offset: bool,
+    /// The RXQ read burst length (`RXCR2::srdbl`). The driver's own RX functions only
+    /// work with [`SpiRxDataBurstLength::SingleFrame`], the default.
+    /// [`SpiRxDataBurstLength::Reserved`] is rejected with [`Error::ReservedFieldValue`].
+    pub rx_burst_length: SpiRxDataBurstLength,
+    /// The interrupts to enable. By default link change, TX complete (see
+    /// [`Chip::configure_tx_interrupt_on_completion`]), RX, RX overrun, TX space available
+    /// and SPI bus error.
+    pub interrupts: InterruptMask,
+}
+
+impl InitConfig {
+    /// The default configuration, as a `const`.
+    pub const fn new() -> Self {
+        Self {
+            rx_filter: RxFilterConfig::new(),
+            tx_auto_pad: true,
+            tx_auto_crc: true,
+            flow_control: true,
+            rx_source_address_filter: false,
+            verify_mac_writes: false,
+            rx_checksum_verification: false,
+            tx_checksum_generation: false,
+            rx_ip_header_offset: true,
+            rx_burst_length: SpiRxDataBurstLength::SingleFrame,
+            interrupts: InterruptMask {
+                link_changed: true,
+                tx_complete: true,
+                rx_ready: true,
+                rx_overrun: true,
+                tx_stopped: false,
+                rx_stopped: false,
+                tx_space_available: true,
+                wakeup_frame_detected: false,
+                magic_packet_detected: false,
+                linkup_detected: false,
+                energy_detected: false,
+                delayed_energy_detected: false,
+                spi_bus_error: true,
+            },
+        }
+    }
+
+    /// Set [`InitConfig::rx_checksum_verification`]
+    #[must_use]
+    pub const fn with_rx_checksum_verification(mut self, enable: bool) -> Self {
+        self.rx_checksum_verification = enable;
+        self
+    }
+
+    /// Set [`InitConfig::tx_checksum_generation`]
+    #[must_use]
+    pub const fn with_tx_checksum_generation(mut self, enable: bool) -> Self {
+        self.tx_checksum_generation = enable;
+        self
+    }
+
+    /// Set [`InitConfig::rx_burst_length`]
+    #[must_use]
+    pub const fn with_spi_burst_length(mut self, burst_length: SpiRxDataBurstLength) -> Self {
+        self.rx_burst_length = burst_length;
+        self
+    }
+
+    /// Set [`InitConfig::interrupts`]
+    #[must_use]
+    pub const fn with_interrupt_mask(mut self, mask: InterruptMask) -> Self {
+        self.interrupts = mask;
+        self
+    }
+}
+
+impl Default for InitConfig {
+    /// See [`InitConfig::new`]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// The register setup shared by `Chip::init_with_config` and `BlockingChip::init_with_config`,
+// applied over the reset values with TX and RX still disabled
+impl InitConfig {
+    fn check(&self) -> Result<(), Error> {
+        if let SpiRxDataBurstLength::Reserved(_) = self.rx_burst_length {
+            return Err(Error::ReservedFieldValue {
+                register: "RXCR2",
+                field: "srdbl",
+            });
+        }
+        Ok(())
+    }
+
+    fn apply_txcr(&self, r: &mut Txcr) {
+        r.set_tcgicmp(false);
+        r.set_tcgtcp(self.tx_checksum_generation);
+        r.set_tcgip(self.tx_checksum_generation);
+        r.set_txfce(false);
+        r.set_txpe(self.tx_auto_pad);
+        r.set_txce(self.tx_auto_crc);
+    }
+
+    fn apply_rxcr1(&self, r: &mut Rxcr1) {
+        r.set_rxudpfcc(self.rx_checksum_verification);
+        r.set_rxtcpfcc(self.rx_checksum_verification);
+        r.set_rxipfcc(self.rx_checksum_verification);
+        // Flow control waits for the link, see `Chip::apply_flow_control`
+        r.set_rxfce(false);
+        self.rx_filter.apply(r);
+    }
+
+    fn apply_rxcr2(&self, r: &mut Rxcr2) {
+        r.set_iufpp(true);
+        r.set_rxiufcez(true);
+        r.set_udplfe(true);
+        r.set_rxicmpfcc(true);
+        r.set_srdbl(self.rx_burst_length);
+    }
+
+    // Along with an `RXFCTR::rxfct` of 1, see `Chip::set_rx_frame_count_threshold`
+    fn apply_rxqcr(&self, r: &mut Rxqcr) {
+        r.set_rxfcte(true);
+        r.set_rxiphtoe(self.rx_ip_header_offset);
+        r.set_adrfe(true);
+    }
+
+    fn ier(&self, tx_interrupt_on_completion: bool) -> Ier {
+        let mut ier: Ier = self.interrupts.into();
+        ier.set_txie(ier.txie() && tx_interrupt_on_completion);
+        ier
+    }
+
+    // What `apply_rxcr1` and `apply_rxcr2` turned on
+    fn rx_checksums(&self) -> RxFrameMetrics {
+        RxFrameMetrics {
+            ip_checksum_verified: self.rx_checksum_verification,
+            tcp_checksum_verified: self.rx_checksum_verification,
+            udp_checksum_verified: self.rx_checksum_verification,
+            icmp_checksum_verified: true,
+        }
+    }
+}
+
+/// A snapshot of the PHY state from `P1MBSR` and `P1SR`, see [`Chip::phy_status`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PhyStatus {
+    pub link_up: bool,
+    pub an_complete: bool,
+    pub an_capable: bool,
+    /// `None` when the link is down
+    pub speed: Option<LinkSpeed>,
+    /// `None` when the link is down
+    pub duplex: Option<Duplex>,
+    /// The auto MDI-X has crossed over the pairs
+    pub mdix_active: bool,
+    pub polarity_reversed: bool,
+    // Our own capabilities, from `P1MBSR`
+    pub t4_capable: bool,
+    pub x100_full_capable: bool,
+    pub x100_half_capable: bool,
+    pub x10_full_capable: bool,
+    pub x10_half_capable: bool,
+}
+
+/// A snapshot of the chip's diagnostic state, see [`Chip::check_health`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct HealthReport {
+    pub link_up: bool,
+    /// Neither the RX nor the TX memory built in self test failed
+    pub bist_ok: bool,
+    pub pending_rx_frames: u8,
+    /// Free TXQ memory, in bytes
+    pub tx_buffer_available: u16,
+    pub spi_error_detected: bool,
+    pub rx_overrun_detected: bool,
+    pub revision_id: u8,
+}
+
+/// The Ethernet CRC-32 of `bytes`, as used for the wake-up frame pattern CRCs.
+fn ethernet_crc32(bytes: impl Iterator<Item = u8>) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for byte in bytes {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// Check a TX frame of `len` bytes isn't over [`MAX_FRAME_SIZE`].
+const fn validate_frame_size(len: usize) -> Result<(), Error> {
+    if len > MAX_FRAME_SIZE {
+        return Err(Error::TxPacketTooBig {
+            size: len,
+            max: MAX_FRAME_SIZE as u16,
+        });
+    }
+    Ok(())
+}
+
+// See `Chip::configure_srdbl_burst_length_from_mtu`
+fn burst_length_for_mtu(mtu: usize) -> SpiRxDataBurstLength {
+    match mtu {
+        0..=64 => SpiRxDataBurstLength::X4Bytes,
+        65..=128 => SpiRxDataBurstLength::X8Bytes,
+        129..=512 => SpiRxDataBurstLength::X16Bytes,
+        513..=1000 => SpiRxDataBurstLength::X32Bytes,
+        _ => SpiRxDataBurstLength::SingleFrame,
+    }
+}
+
+/// The TXQ space a frame of `tx_len` bytes takes up: the 4 byte control word + byte
+/// count header, plus the frame itself padded to a multiple of 4 bytes.
+fn txq_space_needed(tx_len: usize) -> usize {
+    (tx_len + 4 + 3) & !3
+}
+
+fn check_default<F: Into<[u8; 2]>>(
+    register: &'static str,
+    actual: F,
+    expected: F,
+) -> Result<(), Error> {
+    let actual = u16::from_le_bytes(actual.into());
+    let expected = u16::from_le_bytes(expected.into());
+    if actual != expected {
+        return Err(Error::RegisterDefaultMismatch {
+            register,
+            expected,
+            actual,
+        });
+    }
+    Ok(())
+}
+
+// The length of the pad before each received frame, see `InitConfig::rx_ip_header_offset`
+const fn ip_header_pad_len(rx_ip_header_offset: bool) -> u16 {
+    if rx_ip_header_offset { 2 } else { 0 }
+}
+
+// The bytes needed after `len` to make it a multiple of 4, as TXQ writes and RXQ reads must be
+const fn pad_len(len: usize) -> usize {
+    (4 - len % 4) % 4
+}
+
+fn check_chip_id(cider: Cider) -> Result<ChipInfo, Error> {
+    if cider.chip_id() != CHIP_ID_CHIP || cider.family_id() != CHIP_ID_FAMILY {
+        return Err(Error::BadChipId {
+            expected_family: CHIP_ID_FAMILY,
+            actual_family: cider.family_id(),
+            expected_chip: CHIP_ID_CHIP,
+            actual_chip: cider.chip_id(),
+        });
+    }
+    driver_debug!("Found ksz8851snl rev {}", cider.revision_id());
+    Ok(ChipInfo {
+        family_id: cider.family_id(),
+        chip_id: cider.chip_id(),
+        revision_id: cider.revision_id(),
+    })
+}
+
+fn bist_done(mbir: Mbir) -> bool {
+    mbir.txmbf() && mbir.rxmbf()
+}
+
+// The BIST result, once polling for `bist_done` has finished or given up
+fn check_bist(mbir: Mbir) -> Result<(), Error> {
+    if !bist_done(mbir) {
+        return Err(Error::BistTimeout);
+    }
+    if mbir.rxmbfa() || mbir.txmbfa() {
+        return Err(Error::FailedBuiltInSelfTest {
+            rx_bist_failed: mbir.rxmbfa(),
+            tx_bist_failed: mbir.txmbfa(),
+        });
+    }
+    Ok(())
+}
+
+// The control word and byte count written ahead of a TX frame of `len` bytes
+fn tx_frame_header(
+    len: usize,
+    frame_id: u8,
+    interrupt_on_completion: bool,
+) -> (TxCtrlWord, [u8; 2]) {
+    let mut txc = TxCtrlWord::new_zero();
+    txc.set_transmit_interrupt_on_completion(interrupt_on_completion);
+    txc.set_frame_id(frame_id);
+    (txc, (len as u16).to_le_bytes())
+}
+
+// Check the valid (`RXFHSR::rxfv`) frame at the head of the RXQ, returning the length of its
+// frame data. `RxFrameInvalid` and `RxFrameCorrupt` mean the frame must be discarded.
+fn check_rx_frame(
+    frame_status: Rxfhsr,
+    byte_count: u16,
+    ip_header_pad_len: u16,
+    buf_len: usize,
+) -> Result<u16, Error> {
+    if byte_count < ip_header_pad_len + 4
+        || frame_status.rxce()
+        || frame_status.rxrf()
+        || frame_status.rxftl()
+        || frame_status.rxmr()
+        || frame_status.rxudpfcs()
+        || frame_status.rxtcpfcs()
+        || frame_status.rxipfcs()
+        || frame_status.rxicmpfcs()
+    {
+        return Err(Error::RxFrameInvalid);
+    }
+    if byte_count > MAX_RX_FRAME_BYTES {
+        return Err(Error::RxFrameCorrupt { byte_count });
+    }
+    // Only the frame data goes into the caller's buffer, see `Chip::rx_with_info`
+    let frame_len = byte_count - ip_header_pad_len - 4;
+    if usize::from(frame_len) > buf_len {
+        return Err(Error::RxBufferTooSmall {
+            needed: frame_len.into(),
+            provided: buf_len,
+        });
+    }
+    Ok(frame_len)
+}
+
+// The header words come round again at the start of the RXQ DMA read. Not matching the
+// register reads means the read was corrupted or torn, and the frame (already dequeued)
+// can't be trusted.
+fn check_rx_dma_header(
+    frame_status: Rxfhsr,
+    byte_count: u16,
+    status: Rxfhsr,
+    bc: Rxfhbcr,
+) -> Result<(), Error> {
+    if status != frame_status || bc.rxbc() != byte_count {
+        return Err(Error::RxFrameCorrupt { byte_count });
+    }
+    Ok(())
+}
+
+// The MAC address registers holding `mac`, first byte highest
+fn mac_registers(mac: [u8; 6]) -> (Marh, Marm, Marl) {
+    let mut high = Marh::new_zero();
+    high.set_ma_5(mac[0]);
+    high.set_ma_4(mac[1]);
+    let mut med = Marm::new_zero();
+    med.set_ma_3(mac[2]);
+    med.set_ma_2(mac[3]);
+    let mut low = Marl::new_zero();
+    low.set_ma_1(mac[4]);
+    low.set_ma_0(mac[5]);
+    (high, med, low)
+}
+
+fn mac_from_registers(high: Marh, med: Marm, low: Marl) -> [u8; 6] {
+    [
+        high.ma_5(),
+        high.ma_4(),
+        med.ma_3(),
+        med.ma_2(),
+        low.ma_1(),
+        low.ma_0(),
+    ]
+}
+
+// The MAC address read back after writing `written`, see `InitConfig::verify_mac_writes`
+fn check_mac_read_back(written: [u8; 6], read: [u8; 6]) -> Result<(), Error> {
+    if read != written {
+        driver_error!("MAC address read back doesn't match what was written");
+        return Err(Error::MacVerifyFailed);
+    }
+    Ok(())
+}
+
+/// [`Chip`] before [`Chip::init`]: the chip can be reset and checked, but not used to
+/// send or receive. What [`Chip::new`] returns.
+pub enum Uninitialized {}
+
+/// [`Chip`] after [`Chip::init`], with the full API available.
+pub enum Initialized {}
+
+/// A [`Chip`] that hasn't been initialized yet.
+pub type UninitChip<SPI, D> = Chip<SPI, D, Uninitialized>;
+
+/// A [`Chip`] that has been initialized.
+pub type InitChip<SPI, D> = Chip<SPI, D, Initialized>;
+
+/// The KSZ8851SNL driver. `S` is [`Uninitialized`] until [`Chip::init`] is done, so TX
+/// and RX can't be used before then.
+///
+/// The state only covers that first `init`. An [`InitChip`] can still be put back to
+/// its reset state, by [`Chip::reset`] or an `init` that fails part way, without its type
+/// changing. TX and RX then return [`Error::NotInitialized`] until `init` succeeds again.
+pub struct Chip<SPI: SpiDevice, D: DelayNs, S = Initialized> {
+    pub dev: Ksz8851snl<Ksz8851snlInterface<SPI>>,
+    inner: ChipInner<D>,
+    _state: PhantomData<S>,
+}
+
+// The driver state, the same whatever `Chip`'s `S` is
+struct ChipInner<D> {
+    delay: D,
+    next_frame_id: u8,
+    verify_defaults_on_init: bool,
+    rx_ip_header_offset: bool,
+    tx_interrupt_on_completion: bool,
+    link_change_debounce_ms: u32,
+    rx_discard_timeout_us: u32,
+    dma_active: bool,
+    tx_auto_enqueue: bool,
+    pending_tx: Option<PendingTx>,
+    last_tx_frame_id: Option<u8>,
+    rx_overrun_auto_recover: bool,
+    rx_overruns: u32,
+    rx_checksums: RxFrameMetrics,
+    config: InitConfig,
+    // The last MAC address set, to restore after a reset
+    mac: Option<MacAddress>,
+    revision_id: u8,
+    collision_mode: CollisionMode,
+    rx_burst_length: SpiRxDataBurstLength,
+    state: ConnectionState,
+}
+
+// A TX started by `write_tx_register_header`, waiting for `write_tx_payload`.
+#[derive(Copy, Clone)]
+struct PendingTx {
+    ier: Ier,
+    len: usize,
+    frame_id: u8,
+}
+
+impl<SPI: SpiDevice, D: DelayNs, S> From<Chip<SPI, D, S>>
+    for (Ksz8851snl<Ksz8851snlInterface<SPI>>, D)
+{
+    fn from(chip: Chip<SPI, D, S>) -> Self {
+        chip.into_parts()
+    }
+}
+
+impl<SPI: SpiDevice, D: DelayNs> Chip<SPI, D, Uninitialized> {
+    /// Create a new driver from the given SPI device `dev`, to be initialized with the
+    /// default [`InitConfig`].
+    #[must_use]
+    pub fn new(dev: SPI, delay: D) -> Self {
+        Self::new_with_config(dev, delay, InitConfig::default())
+    }
+
+    /// Create a new driver from the given SPI device `dev`, to be initialized with `config`
+    /// by [`Chip::init`].
+    #[must_use]
+    pub fn new_with_config(dev: SPI, delay: D, config: InitConfig) -> Self {
+        Self {
+            dev: Ksz8851snl::new(Ksz8851snlInterface { bus: dev }),
+            inner: ChipInner {
+                delay,
+                next_frame_id: 0,
+                verify_defaults_on_init: false,
+                rx_ip_header_offset: config.rx_ip_header_offset,
+                tx_interrupt_on_completion: true,
+                link_change_debounce_ms: 0,
+                rx_discard_timeout_us: RX_DISCARD_TIMEOUT_US,
+                dma_active: false,
+                tx_auto_enqueue: false,
+                pending_tx: None,
+                last_tx_frame_id: None,
+                rx_overrun_auto_recover: false,
+                rx_overruns: 0,
+                rx_checksums: RxFrameMetrics::default(),
+                config,
+                mac: None,
+                revision_id: 0,
+                collision_mode: CollisionMode::Discard,
+                rx_burst_length: SpiRxDataBurstLength::SingleFrame,
+                state: ConnectionState::Uninitialized,
+            },
+            _state: PhantomData,
+        }
+    }
+
+    /// Initialize the chip with the configuration given to [`Chip::new_with_config`] (the
+    /// default for [`Chip::new`]), giving the initialized [`Chip`] that can send and
+    /// receive. See [`Chip::init_with_config`].
+    pub async fn init(self) -> Result<Chip<SPI, D>, Error> {
+        let config = self.inner.config;
+        self.init_with_config(&config).await
+    }
+
+    /// Initialize the chip with `config`, giving the initialized [`Chip`] that can send and
+    /// receive. `config` becomes its stored configuration, see [`Chip::config`].
+    pub async fn init_with_config(self, config: &InitConfig) -> Result<Chip<SPI, D>, Error> {
+        let mut chip: Chip<SPI, D> = Chip {
+            dev: self.dev,
+            inner: self.inner,
+            _state: PhantomData,
+        };
+        chip.init_with_config(config).await?;
+        Ok(chip)
+    }
+}
+
+impl<SPI: SpiDevice, D: DelayNs, S> Chip<SPI, D, S> {
+    /// Split the driver into the register level device and the delay, e.g. to use
+    /// registers that the driver doesn't expose. The driver's own state (frame IDs, DMA
+    /// tracking, configuration...) is discarded.
+    #[must_use]
+    pub fn into_parts(self) -> (Ksz8851snl<Ksz8851snlInterface<SPI>>, D) {
+        (self.dev, self.inner.delay)
+    }
+
+    /// Give back the SPI device passed to [`Chip::new`], e.g. to share the bus with
+    /// something else once done with the chip. The delay and the driver's state are
+    /// dropped, see [`Chip::into_parts`] to keep the delay. The chip is left as it is, so
+    /// may still be sending and receiving.
+    #[must_use]
+    pub fn into_inner(self) -> SPI {
+        self.dev.interface.into_inner()
+    }
+
+    /// The SPI interface, wrapping the SPI device as [`Ksz8851snlInterface::bus`].
+    #[must_use]
+    pub fn interface(&self) -> &Ksz8851snlInterface<SPI> {
+        &self.dev.interface
+    }
+
+    /// The SPI interface, e.g. to run your own `bus.transaction()`s for TXQ/RXQ DMA.
+    ///
+    /// The driver doesn't know about anything done through this: leave the chip as you
+    /// found it, in particular with no queue DMA (`RXQCR::sda`) still active.
+    #[must_use]
+    pub fn interface_mut(&mut self) -> &mut Ksz8851snlInterface<SPI> {
+        &mut self.dev.interface
+    }
+
+    /// Make [`Chip::init`] check register reset values (see
+    /// [`Chip::verify_register_defaults`]) straight after resetting the chip.
+    pub fn set_verify_defaults_on_init(&mut self, verify: bool) {
+        self.inner.verify_defaults_on_init = verify;
+    }
+
+    /// Perform a global soft reset, returning every register to its default value.
+    ///
+    /// This leaves an [`InitChip`] unconfigured, see [`Chip`]: call [`Chip::init`] (or
+    /// use [`Chip::reset_and_reconfigure`]) before sending or receiving again.
+    pub async fn reset(&mut self) -> Result<(), Error> {
+        if self.inner.dma_active {
+            // Registers (including GRR) are inaccessible until DMA is closed
+            self.end_dma().await?;
+        }
+        self.inner.pending_tx = None;
+        self.inner.last_tx_frame_id = None;
+        self.inner.rx_checksums = RxFrameMetrics::default();
+        self.dev
+            .grr()
+            .write_async(|grr| grr.set_global_soft_reset(true))
+            .await?;
+        self.inner.delay.delay_ms(10).await;
+        self.dev.grr().write_with_zero_async(|_| {}).await?;
+        self.inner.delay.delay_ms(10).await;
+        self.inner.state = ConnectionState::Uninitialized;
+        Ok(())
+    }
+
+    /// Reset only the QMU (`GRR::qmu_module_soft_reset`), flushing every frame in the TXQ
+    /// and RXQ, e.g. after an overrun. Unlike [`Chip::reset`] the PHY and MAC are left
+    /// alone, so the link stays up and the MAC address is kept.
+    ///
+    /// The QMU reset also returns the QMU registers to their defaults, so the queue and
+    /// TX/RX configuration the driver set up (`TXCR`, `RXCR1`, `RXCR2`, `TXQCR`, `RXQCR`,
+    /// `TXFDPR`, `RXFDPR`, `RXFCTR` and `IER`) is read beforehand and written back
+    /// afterwards. Nothing is reconfigured from the [`InitConfig`].
+    pub async fn soft_reset(&mut self) -> Result<(), Error> {
+        if self.inner.dma_active {
+            // Registers (including GRR) are inaccessible until DMA is closed
+            self.end_dma().await?;
+        }
+        let mut txcr = self.dev.txcr().read_async().await?;
+        let mut rxcr1 = self.dev.rxcr_1().read_async().await?;
+        let rxcr2 = self.dev.rxcr_2().read_async().await?;
+        let mut txqcr = self.dev.txqcr().read_async().await?;
+        let mut rxqcr = self.dev.rxqcr().read_async().await?;
+        let txfdpr = self.dev.txfdpr().read_async().await?;
+        let mut rxfdpr = self.dev.rxfdpr().read_async().await?;
+        let rxfct = self.dev.rxfctr().read_async().await?.rxfct();
+        let ier = self.dev.ier().read_async().await?;
+        // Don't write back any of the one-shot command bits
+        txcr.set_ftxq(false);
+        rxcr1.set_frxq(false);
+        txqcr.set_metfe(false);
+        txqcr.set_txqmam(false);
+        rxqcr.set_sda(false);
+        rxqcr.set_rrxef(false);
+        rxfdpr.set_rxfp(0);
+
+        self.inner.pending_tx = None;
+        self.inner.last_tx_frame_id = None;
+        self.dev
+            .grr()
+            .write_with_zero_async(|grr| grr.set_qmu_module_soft_reset(true))
+            .await?;
+        self.inner.delay.delay_ms(10).await;
+        self.dev.grr().write_with_zero_async(|_| {}).await?;
+        self.inner.delay.delay_ms(10).await;
+
+        self.dev.rxfdpr().write_async(|r| *r = rxfdpr).await?;
+        self.dev.txfdpr().write_async(|r| *r = txfdpr).await?;
+        self.dev
+            .rxfctr()
+            .write_with_zero_async(|r| r.set_rxfct(rxfct))
+            .await?;
+        self.dev.rxqcr().write_async(|r| *r = rxqcr).await?;
+        self.dev.txqcr().write_async(|r| *r = txqcr).await?;
+        self.dev.rxcr_2().write_async(|r| *r = rxcr2).await?;
+        self.dev.ier().write_async(|r| *r = ier).await?;
+        // TX and RX enables last, once everything else is back
+        self.dev.txcr().write_async(|r| *r = txcr).await?;
+        self.dev.rxcr_1().write_async(|r| *r = rxcr1).await?;
+        Ok(())
+    }
+
+    /// Check that a selection of registers hold their datasheet reset values.
+    ///
+    /// Only meaningful straight after [`Chip::reset`]. A mismatch usually points at a
+    /// hardware or wiring fault (e.g. a stuck MISO line or the wrong SPI mode) rather than
+    /// a driver problem, and is reported as [`Error::RegisterDefaultMismatch`].
+    pub async fn verify_register_defaults(&mut self) -> Result<(), Error> {
+        check_default("TXCR", self.dev.txcr().read_async().await?, Txcr::new())?;
+        check_default("RXCR1", self.dev.rxcr_1().read_async().await?, Rxcr1::new())?;
+        check_default("RXCR2", self.dev.rxcr_2().read_async().await?, Rxcr2::new())?;
+        check_default("RXQCR", self.dev.rxqcr().read_async().await?, Rxqcr::new())?;
+        check_default("IER", self.dev.ier().read_async().await?, Ier::new())?;
+        Ok(())
+    }
+
+    /// Reset the chip, then check that it is what we think it is and that its built in
+    /// self test passed, without configuring anything else.
+    ///
+    /// Handy for board bring-up and manufacturing tests, to check the chip is soldered
+    /// down and talking SPI before running the full [`Chip::init`].
+    pub async fn assert_chip_present(&mut self) -> Result<ChipInfo, Error> {
+        self.reset().await?;
+        self.check_chip().await
+    }
+
+    /// Read the chip's diagnostic registers in one go. This is the first thing to call
+    /// when bringing up a new board.
+    ///
+    /// This doesn't change any chip state (pending interrupts are left pending) so it can
+    /// be used both before and after [`Chip::init`].
+    pub async fn check_health(&mut self) -> Result<HealthReport, Error> {
+        let mbsr = self.dev.p_1_mbsr().read_async().await?;
+        let mbir = self.dev.mbir().read_async().await?;
+        let rxfctr = self.dev.rxfctr().read_async().await?;
+        let txmir = self.dev.txmir().read_async().await?;
+        let isr = self.dev.isr().read_async().await?;
+        let cider = self.dev.cider().read_async().await?;
+        Ok(HealthReport {
+            link_up: mbsr.link_status(),
+            bist_ok: !mbir.rxmbfa() && !mbir.txmbfa(),
+            pending_rx_frames: rxfctr.rxfc(),
+            tx_buffer_available: txmir.txma(),
+            spi_error_detected: isr.spibeis(),
+            rx_overrun_detected: isr.rxois(),
+            revision_id: cider.revision_id(),
+        })
+    }
+
+    async fn check_chip(&mut self) -> Result<ChipInfo, Error> {
+        let info = check_chip_id(self.dev.cider().read_async().await?)?;
+        self.inner.revision_id = info.revision_id;
+        self.wait_bist_complete(BIST_TIMEOUT_MS).await?;
+        Ok(info)
+    }
+
+    /// Wait up to `timeout_ms` for the TX and RX memory built in self tests, which run
+    /// after every reset, to finish, then check that they passed.
+    ///
+    /// Returns [`Error::BistTimeout`] if they haven't finished in time, or
+    /// [`Error::FailedBuiltInSelfTest`] if either failed.
+    pub async fn wait_bist_complete(&mut self, timeout_ms: u32) -> Result<(), Error> {
+        let mut mbir = self.dev.mbir().read_async().await?;
+        for _ in 0..timeout_ms {
+            if bist_done(mbir) {
+                break;
+            }
+            self.inner.delay.delay_ms(1).await;
+            mbir = self.dev.mbir().read_async().await?;
+        }
+        check_bist(mbir)
+    }
+
+    /// Check SPI communication by writing a test pattern to `MARL` (the low bytes of the
+    /// MAC address), reading it back, and restoring the original value.
+    ///
+    /// This needs no network and works before [`Chip::init`], so it's a good first step
+    /// when bringing up new hardware: a wrong SPI mode or clock, swapped MOSI/MISO or a
+    /// misbehaving chip select all show up as a mismatch. Don't use it while receiving, as
+    /// the MAC address filter briefly sees the test pattern.
+    pub async fn test_spi_loopback(&mut self) -> Result<SpiLoopbackResult, Error> {
+        const PATTERN: u16 = 0x5aa5;
+        let original = self.dev.marl().read_async().await?;
+        self.dev
+            .marl()
+            .write_async(|r| {
+                r.set_ma_0(PATTERN as u8);
+                r.set_ma_1((PATTERN >> 8) as u8);
+            })
+            .await?;
+        let read_value = u16::from_le_bytes(self.dev.marl().read_async().await?.into());
+        self.dev.marl().write_async(|r| *r = original).await?;
+        Ok(SpiLoopbackResult {
+            write_value: PATTERN,
+            read_value,
+            passed: read_value == PATTERN,
+        })
+    }
+
+    /// Check that the register at `addr` holds the bits in `mask` as written: writes
+    /// `test_value` to those bits (leaving the others as they were), reads it back, and
+    /// restores the original value. Returns whether the read back bits matched.
+    ///
+    /// This is for manufacturing tests. Only use it on registers where the masked bits are
+    /// plain read/write storage, as the test value does briefly take effect. Returns
+    /// [`Error::InvalidRegisterAddress`] if `addr` isn't 2-aligned.
+    pub async fn read_write_register_test(
+        &mut self,
+        addr: u8,
+        test_value: u16,
+        mask: u16,
+    ) -> Result<bool, Error> {
+        if addr & 1 != 0 {
+            return Err(Error::InvalidRegisterAddress { address: addr });
+        }
+        let mut original = [0u8; 2];
+        self.dev
+            .interface
+            .read_register(addr, 16, &mut original)
+            .await?;
+        let original = u16::from_le_bytes(original);
+        let written = (original & !mask) | (test_value & mask);
+        self.dev
+            .interface
+            .write_register(addr, 16, &written.to_le_bytes())
+            .await?;
+        let mut read_back = [0u8; 2];
+        self.dev
+            .interface
+            .read_register(addr, 16, &mut read_back)
+            .await?;
+        self.dev
+            .interface
+            .write_register(addr, 16, &original.to_le_bytes())
+            .await?;
+        Ok(u16::from_le_bytes(read_back) & mask == test_value & mask)
+    }
+
+    /// Run [`Chip::read_write_register_test`] over a set of plain read/write registers
+    /// (the MAC address, multicast hash table and flow control watermarks), writing both
+    /// 0x5AA5 and 0xA55A to each so every bit is tested both set and clear.
+    ///
+    /// Stuck, flipped or forgetful bits all indicate a hardware fault. Every register is
+    /// restored afterwards, but don't run this while receiving: the MAC address and
+    /// filters briefly hold test values.
+    pub async fn run_register_loopback_tests(&mut self) -> Result<RegisterTestResults, Error> {
+        let mut results =
+            LOOPBACK_TEST_REGISTERS.map(|(register, address, _)| RegisterTestResult {
+                register,
+                address,
+                passed: true,
+            });
+        for (result, (_, address, mask)) in results.iter_mut().zip(LOOPBACK_TEST_REGISTERS) {
+            for pattern in LOOPBACK_TEST_PATTERNS {
+                if !self
+                    .read_write_register_test(address, pattern, mask)
+                    .await?
+                {
+                    result.passed = false;
+                }
+            }
+        }
+        Ok(RegisterTestResults { results })
+    }
+
+    /// The configuration [`Chip::init`] applies.
+    #[must_use]
+    pub fn config(&self) -> &InitConfig {
+        &self.inner.config
+    }
+
+    /// The silicon revision (`CIDER::revision_id`), as read by [`Chip::init`] or
+    /// [`Chip::assert_chip_present`]. 0 before either has run.
+    #[must_use]
+    pub fn get_revision_id(&self) -> u8 {
+        self.inner.revision_id
+    }
+
+    /// The current connection state, as last updated by [`Chip::advance`].
+    #[must_use]
+    pub fn state(&self) -> ConnectionState {
+        self.inner.state
+    }
+
+    // `RXQCR::sda` opens DMA access to *both* queues: whether the FIFO access that follows
+    // is an RXQ read or a TXQ write is decided by its opcode, and while it is set the chip
+    // ignores all register accesses apart from RXQCR itself. `&mut self` already stops
+    // `tx()` and `rx()` overlapping, but a future dropped mid-transfer can leave DMA
+    // open, so we track it and refuse to start another transfer on top of it.
+    async fn start_dma(&mut self) -> Result<(), Error> {
+        if self.inner.dma_active {
+            return Err(Error::DmaConflict);
+        }
+        self.dev.rxqcr().modify_async(|r| r.set_sda(true)).await?;
+        self.inner.dma_active = true;
+        Ok(())
+    }
+
+    async fn end_dma(&mut self) -> Result<(), Error> {
+        self.dev.rxqcr().modify_async(|r| r.set_sda(false)).await?;
+        self.inner.dma_active = false;
+        Ok(())
+    }
+}
+
+impl<SPI: SpiDevice, D: DelayNs> Chip<SPI, D> {
+    /// Initialize the chip again with the stored configuration (see [`Chip::config`]): the
+    /// one given to [`Chip::new_with_config`], the default for [`Chip::new`], or the last
+    /// one passed to [`Chip::init_with_config`]. E.g. after [`Chip::reset`], which leaves
+    /// TX and RX returning [`Error::NotInitialized`] until this is done.
+    pub async fn init(&mut self) -> Result<(), Error> {
+        let config = self.inner.config;
+        self.init_with_config(&config).await
+    }
+
+    /// Reset the chip and bring it back to how the driver last set it up: [`Chip::init`]
+    /// with the stored configuration, then the MAC address last given to
+    /// [`Chip::set_mac`] (the chip forgets it on reset), then the
+    /// [`InitConfig::rx_source_address_filter`] that needs it.
+    ///
+    /// Settings changed through other methods since then aren't restored. This is the
+    /// one-step recovery from e.g. [`Error::DmaConflict`] or a chip that stopped responding.
+    pub async fn reset_and_reconfigure(&mut self) -> Result<(), Error> {
+        self.init().await?;
+        if let Some(mac) = self.inner.mac {
+            self.set_mac_and_restart(mac).await?;
+            if self.inner.config.rx_source_address_filter {
+                self.configure_rx_source_address_filter(true).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Initialize the chip again with `config`, which also becomes the stored configuration
+    /// for later calls to [`Chip::init`].
+    ///
+    /// This (and the first `init` of an [`UninitChip`]):
+    /// - Resets the chip
+    /// - Optionally checks register reset values (see [`Chip::set_verify_defaults_on_init`])
+    /// - Checks that it is what we think it is
+    /// - Waits for the selftest to finish and checks it passed
+    /// - Configures RX and TX functions, as given by `config`
+    /// - Enables RX and TX
+    pub async fn init_with_config(&mut self, config: &InitConfig) -> Result<(), Error> {
+        config.check()?;
+        self.inner.config = *config;
+        self.reset().await?;
+        if self.inner.verify_defaults_on_init {
+            self.verify_register_defaults().await?;
+        }
+        self.check_chip().await?;
+
+        self.dev
+            .txfdpr()
+            .modify_async(|r| r.set_txfpai(true))
+            .await?;
+
+        self.dev
+            .txcr()
+            .modify_async(|r| config.apply_txcr(r))
+            .await?;
+
+        // Configure rx interrupt to be every 10ms at most. TODO: is this sufficient?
+        // self.dev
+        //     .write_register(RXDTTR::zeroed().with_receive_duration_timer_threshold(1000))
+        //     .await
+        //     .unwrap();
+
+        self.dev
+            .rxfdpr()
+            .modify_async(|r| r.set_rxfpai(true))
+            .await?;
+        self.dev
+            .rxfctr()
+            .write_with_zero_async(|r| r.set_rxfct(1))
+            .await?;
+        self.dev
+            .rxqcr()
+            .modify_async(|r| config.apply_rxqcr(r))
+            .await?;
+        self.inner.rx_ip_header_offset = config.rx_ip_header_offset;
+
+        self.dev
+            .rxcr_1()
+            .modify_async(|r| config.apply_rxcr1(r))
+            .await?;
+        self.dev
+            .rxcr_2()
+            .modify_async(|r| config.apply_rxcr2(r))
+            .await?;
+        self.inner.rx_burst_length = config.rx_burst_length;
+        self.inner.rx_checksums = config.rx_checksums();
+        if config.rx_source_address_filter && self.get_mac().await? != [0; 6] {
+            self.configure_rx_source_address_filter(true).await?;
+        }
+
+        let ier = config.ier(self.inner.tx_interrupt_on_completion);
+        self.dev.ier().write_async(|r| *r = ier).await?;
+
+        // There are two ways to transmit - auto enqueue and manual enqueue.
+        // Auto enqueue involves setting TXQCR[2] at init time, and means you can (supposedly)
+        // write multiple frames at once. Manual enqueue involves setting TXQCR[0] *after*
+        // you've written the frame to transmit. We default to manual, see
+        // `configure_tx_burst()` for why.
+        self.dev
+            .txqcr()
+            .modify_async(|r| r.set_aetfe(self.inner.tx_auto_enqueue))
+            .await?;
+
+        self.dev.txcr().modify_async(|r| r.set_txe(true)).await?;
+
+        self.dev.rxcr_1().modify_async(|r| r.set_rxe(true)).await?;
+
+        self.inner.state = ConnectionState::Disconnected;
+        Ok(())
+    }
+
+    /// Drop received frames whose source address is our own MAC address (`RXCR2::rxsaf`),
+    /// e.g. our own broadcasts looped back by a misbehaving network.
+    ///
+    /// Enabling it returns [`Error::MacNotConfigured`] if the MAC address is still all
+    /// zeros, so set it (see [`Chip::set_mac`]) first.
+    pub async fn configure_rx_source_address_filter(&mut self, enable: bool) -> Result<(), Error> {
+        if enable && self.get_mac().await? == [0; 6] {
+            return Err(Error::MacNotConfigured);
+        }
+        self.dev
+            .rxcr_2()
+            .modify_async(|r| r.set_rxsaf(enable))
+            .await
+            .map_err(Into::into)
+    }
+
+    /// ICMP checksum offload: generate the checksum of transmitted ICMP frames
+    /// (`TXCR::tcgicmp`) and/or verify it on received ones (`RXCR2::rxicmpfcc`, on after
+    /// [`Chip::init`]). Received frames failing the check are dropped.
+    ///
+    /// Both directions only handle non-fragmented frames: fragments are sent with the
+    /// checksum as given, and aren't checked on receive. `RXCR2::iufpp` (set by `init`)
+    /// also passes fragments through the receive checks whatever `rxicmpfcc` says.
+    pub async fn configure_icmp_checksum_offload(
+        &mut self,
+        tx_enable: bool,
+        rx_enable: bool,
+    ) -> Result<(), Error> {
+        self.dev
+            .txcr()
+            .modify_async(|r| r.set_tcgicmp(tx_enable))
+            .await?;
+        self.dev
+            .rxcr_2()
+            .modify_async(|r| r.set_rxicmpfcc(rx_enable))
+            .await?;
+        self.inner.rx_checksums.icmp_checksum_verified = rx_enable;
+        Ok(())
+    }
+
+    /// Set which frames the receiver accepts, see [`RxFilterConfig`].
+    pub async fn set_rx_filter(&mut self, filter: &RxFilterConfig) -> Result<(), Error> {
+        self.dev
+            .rxcr_1()
+            .modify_async(|r| filter.apply(r))
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Receive all broadcast frames (`RXCR1::rxbe`).
+    pub async fn set_receive_broadcast(&mut self, enable: bool) -> Result<(), Error> {
+        self.dev
+            .rxcr_1()
+            .modify_async(|r| r.set_rxbe(enable))
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Receive all multicast frames, including broadcast (`RXCR1::rxme`).
+    pub async fn set_receive_multicast(&mut self, enable: bool) -> Result<(), Error> {
+        self.dev
+            .rxcr_1()
+            .modify_async(|r| r.set_rxme(enable))
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Receive unicast frames addressed to our MAC address (`RXCR1::rxue`).
+    pub async fn set_receive_unicast(&mut self, enable: bool) -> Result<(), Error> {
+        self.dev
+            .rxcr_1()
+            .modify_async(|r| r.set_rxue(enable))
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Receive every frame regardless of its destination address, i.e. promiscuous mode
+    /// (`RXCR1::rxae`). This is independent of the broadcast/multicast/unicast enables.
+    pub async fn set_receive_all(&mut self, enable: bool) -> Result<(), Error> {
+        self.dev
+            .rxcr_1()
+            .modify_async(|r| r.set_rxae(enable))
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Set how unicast and multicast frames are filtered by destination address.
+    ///
+    /// | `unicast`   | `multicast`    | `rxue` | `rxpafma` | `rxme` | `rxmafma` |
+    /// |-------------|----------------|--------|-----------|--------|-----------|
+    /// | `Disabled`  |                | 0      | 0         |        |           |
+    /// | `Perfect`   |                | 1      | 1         |        |           |
+    /// |             | `Disabled`     |        |           | 0      | 0         |
+    /// |             | `HashFilter`   |        |           | 1      | 1         |
+    /// |             | `AllMulticast` |        |           | 1      | 0         |
+    ///
+    /// `HashFilter` only passes anything once the hash table (`MAHTR0`-`MAHTR3`, all
+    /// zeros after reset) has bits set. Broadcast and promiscuous reception are separate,
+    /// see [`Chip::set_receive_broadcast`] and [`Chip::set_receive_all`].
+    pub async fn configure_mac_filter(
+        &mut self,
+        unicast: UnicastFilter,
+        multicast: MulticastFilter,
+    ) -> Result<(), Error> {
+        self.dev
+            .rxcr_1()
+            .modify_async(|r| {
+                let perfect = unicast == UnicastFilter::Perfect;
+                r.set_rxue(perfect);
+                r.set_rxpafma(perfect);
+                r.set_rxme(multicast != MulticastFilter::Disabled);
+                r.set_rxmafma(multicast == MulticastFilter::HashFilter);
+            })
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Drive the connection state machine one step, and return the new state.
+    ///
+    /// Call this regularly from your event loop. It:
+    /// - Initializes the chip from [`ConnectionState::Uninitialized`]
+    /// - Polls the link status from [`ConnectionState::Disconnected`]
+    /// - Waits (1ms per call) for auto-negotiation to complete from
+    ///   [`ConnectionState::Connecting`], giving up and going back to `Disconnected` on
+    ///   timeout
+    /// - Watches for link change interrupts (`ISR::lcis`) from
+    ///   [`ConnectionState::Connected`]
+    ///
+    /// N.B. the link change interrupt is acknowledged here, so don't rely on seeing it in
+    /// [`Chip::handle_interrupt`] while using this.
+    pub async fn advance(&mut self) -> Result<ConnectionState, Error> {
+        self.inner.state = match self.inner.state {
+            ConnectionState::Uninitialized => {
+                self.init().await?;
+                ConnectionState::Disconnected
+            }
+            ConnectionState::Disconnected => {
+                if self.dev.p_1_sr().read_async().await?.link_good() {
+                    ConnectionState::Connecting {
+                        timeout_remaining: AN_TIMEOUT_MS,
+                    }
+                } else {
+                    ConnectionState::Disconnected
+                }
+            }
+            ConnectionState::Connecting { timeout_remaining } => {
+                let p1sr = self.dev.p_1_sr().read_async().await?;
+                if !p1sr.link_good() || timeout_remaining == 0 {
+                    ConnectionState::Disconnected
+                } else if p1sr.an_done() {
+                    let (speed, duplex) = link_mode(p1sr);
+                    self.apply_flow_control(duplex).await?;
+                    ConnectionState::Connected { speed, duplex }
+                } else {
+                    self.inner.delay.delay_ms(1).await;
+                    ConnectionState::Connecting {
+                        timeout_remaining: timeout_remaining - 1,
+                    }
+                }
+            }
+            ConnectionState::Connected { speed, duplex } => {
+                if self.dev.isr().read_async().await?.lcis() {
+                    self.dev
+                        .isr()
+                        .write_with_zero_async(|r| r.set_lcis(true))
+                        .await?;
+                    let p1sr = self.dev.p_1_sr().read_async().await?;
+                    if p1sr.link_good() {
+                        let (speed, duplex) = link_mode(p1sr);
+                        self.apply_flow_control(duplex).await?;
+                        ConnectionState::Connected { speed, duplex }
+                    } else {
+                        ConnectionState::Disconnected
+                    }
+                } else {
+                    ConnectionState::Connected { speed, duplex }
+                }
+            }
+        };
+        Ok(self.inner.state)
+    }
+
+    /// Restart the PHY without resetting the MAC or QMU.
+    ///
+    /// This briefly disables the PHY transmitter, then restarts auto-negotiation and waits
+    /// for it to complete. The MAC address, queues and all other configuration are
+    /// preserved, so this is a cheap way to recover from PHY level problems such as a
+    /// failed auto-negotiation.
+    ///
+    /// Returns [`Error::AutoNegotiationTimeout`] if auto-negotiation doesn't complete,
+    /// e.g. because there is no cable plugged in.
+    pub async fn soft_reset_phy(&mut self) -> Result<(), Error> {
+        self.dev
+            .p_1_mbcr()
+            .modify_async(|r| r.set_disable_transmit(true))
+            .await?;
+        self.inner.delay.delay_ms(1).await;
+        self.dev
+            .p_1_mbcr()
+            .modify_async(|r| {
+                r.set_disable_transmit(false);
+                r.set_restart_an(true);
+            })
+            .await?;
+        for _ in 0..AN_TIMEOUT_MS {
+            if self.dev.p_1_mbsr().read_async().await?.an_complete() {
+                return Ok(());
+            }
+            self.inner.delay.delay_ms(1).await;
+        }
+        Err(Error::AutoNegotiationTimeout)
+    }
+
+    /// Read the interrupt status register and acknowledge every pending interrupt.
+    pub async fn isr_read_and_clear(&mut self) -> Result<Isr, Error> {
+        let isr = self.get_isr_without_clearing().await?;
+        self.clear_isr_bits(isr).await?;
+        Ok(isr)
+    }
+
+    /// Read the interrupt status register, leaving every interrupt pending, e.g. for
+    /// diagnostics.
+    pub async fn get_isr_without_clearing(&mut self) -> Result<Isr, Error> {
+        Ok(self.dev.isr().read_async().await?)
+    }
+
+    /// Acknowledge the interrupts set in `mask`, leaving any others pending.
+    pub async fn clear_isr_bits(&mut self, mask: Isr) -> Result<(), Error> {
+        // ISR bits are write-1-to-clear, so the zeros in `mask` don't touch anything
+        self.dev
+            .isr()
+            .write_async(|r| *r = mask)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Service an interrupt from the chip: acknowledge everything pending and report
+    /// what happened. Call this when the INT pin is asserted.
+    ///
+    /// An RX overrun is reported as [`InterruptStatus::rx_overrun`] along with whatever
+    /// else was pending, and is also recovered from here if enabled with
+    /// [`Chip::configure_rx_overrun_recovery`]. Link changes can be debounced, see
+    /// [`Chip::configure_link_change_interrupt`].
+    pub async fn handle_interrupt(&mut self) -> Result<InterruptStatus, Error> {
+        let status: InterruptStatus = self.isr_read_and_clear().await?.into();
+        if status.link_changed && self.inner.link_change_debounce_ms > 0 {
+            self.debounce_link_change().await?;
+        }
+        if status.rx_overrun && self.inner.rx_overrun_auto_recover {
+            self.recover_rx_overrun().await?;
+        }
+        Ok(status)
+    }
+
+    /// Enable or disable the link change interrupt (`IER::lcie`, on after [`Chip::init`]),
+    /// and set how long [`Chip::handle_interrupt`] debounces it for.
+    ///
+    /// A cable being plugged in can make the link go up and down a few times before it
+    /// settles. With a non-zero `debounce_ms`, `handle_interrupt` doesn't return on a link
+    /// change until there has been none for `debounce_ms`, acknowledging any more that
+    /// come in meanwhile, and reports them all as one
+    /// [`InterruptStatus::link_changed`]. It gives up waiting for the link to settle after
+    /// 5 seconds. Other interrupts arriving meanwhile are left pending for the next call.
+    pub async fn configure_link_change_interrupt(
+        &mut self,
+        enable: bool,
+        debounce_ms: u32,
+    ) -> Result<(), Error> {
+        self.dev.ier().modify_async(|r| r.set_lcie(enable)).await?;
+        self.inner.link_change_debounce_ms = debounce_ms;
+        Ok(())
+    }
+
+    // Wait for `link_change_debounce_ms` without a link change, up to `AN_TIMEOUT_MS`.
+    async fn debounce_link_change(&mut self) -> Result<(), Error> {
+        let mut quiet_ms = 0;
+        for _ in 0..AN_TIMEOUT_MS {
+            if quiet_ms >= self.inner.link_change_debounce_ms {
+                break;
+            }
+            self.inner.delay.delay_ms(1).await;
+            if self.get_isr_without_clearing().await?.lcis() {
+                let mut lcis = Isr::new_zero();
+                lcis.set_lcis(true);
+                self.clear_isr_bits(lcis).await?;
+                quiet_ms = 0;
+            } else {
+                quiet_ms += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Choose how [`Chip::handle_interrupt`] deals with an RX overrun (`ISR::rxois`).
+    ///
+    /// Either way the overrun is reported as [`InterruptStatus::rx_overrun`]. With
+    /// `auto_recover` it calls [`Chip::recover_rx_overrun`] itself, otherwise (the default)
+    /// recovery is left to the caller. Every frame in the RXQ at the time of the overrun is
+    /// lost regardless.
+    pub fn configure_rx_overrun_recovery(&mut self, auto_recover: bool) {
+        self.inner.rx_overrun_auto_recover = auto_recover;
+    }
+
+    /// Recover from an RX overrun by draining the RXQ with [`Chip::drain_rx_queue`], which
+    /// drops every frame in it. Counts towards [`Chip::rx_overruns`].
+    pub async fn recover_rx_overrun(&mut self) -> Result<(), Error> {
+        self.drain_rx_queue().await?;
+        self.inner.rx_overruns = self.inner.rx_overruns.wrapping_add(1);
+        Ok(())
+    }
+
+    /// Discard every frame in the RXQ by flushing it (`RXCR1::frxq`), e.g. during error
+    /// recovery. RX is stopped for the flush, and restarted afterwards if it was enabled.
+    ///
+    /// The alternative is calling [`Chip::rx_discard_frame`] once per frame in
+    /// [`Chip::rx_frames_available`]. That keeps RX running, but costs a few register
+    /// accesses (and the time for the chip to release each frame) per frame, and relies
+    /// on the queue being intact. The flush takes four register writes however full the
+    /// RXQ is and also resets the frame pointers, so it is the one to use after an
+    /// overrun, but any frame arriving while RX is stopped is lost too.
+    pub async fn drain_rx_queue(&mut self) -> Result<(), Error> {
+        let rx_enabled = self.dev.rxcr_1().read_async().await?.rxe();
+        // RXE must be cleared before flushing
+        self.dev.rxcr_1().modify_async(|r| r.set_rxe(false)).await?;
+        self.dev.rxcr_1().modify_async(|r| r.set_frxq(true)).await?;
+        self.dev
+            .rxcr_1()
+            .modify_async(|r| r.set_frxq(false))
+            .await?;
+        if rx_enabled {
+            self.dev.rxcr_1().modify_async(|r| r.set_rxe(true)).await?;
+        }
+        Ok(())
+    }
+
+    /// Discard the frame at the head of the RXQ without reading it (`RXQCR::rrxef`), and
+    /// wait for the chip to release it.
+    ///
+    /// Returns [`Error::RxDiscardTimeout`] if the chip hasn't released it in time (see
+    /// [`Chip::configure_rx_discard_timeout`]), which points at a stuck chip or a bad SPI
+    /// bus. [`Chip::reset_and_reconfigure`] recovers.
+    pub async fn rx_discard_frame(&mut self) -> Result<(), Error> {
+        self.dev.rxqcr().modify_async(|r| r.set_rrxef(true)).await?;
+        // We need to wait until this is cleared before trying to rx again
+        for _ in 0..self.inner.rx_discard_timeout_us {
+            if !self.dev.rxqcr().read_async().await?.rrxef() {
+                return Ok(());
+            }
+            self.inner.delay.delay_us(1).await;
+        }
+        if self.dev.rxqcr().read_async().await?.rrxef() {
+            return Err(Error::RxDiscardTimeout);
+        }
+        Ok(())
+    }
+
+    /// Set how long [`Chip::rx_discard_frame`] (and so RX of a bad frame) waits for the
+    /// chip to release the frame, in microseconds. The default is 1ms, where the release
+    /// normally takes a few register reads.
+    ///
+    /// The wait is a poll of `RXQCR` with a 1us delay in between, so the register reads
+    /// make the real timeout somewhat longer.
+    pub fn configure_rx_discard_timeout(&mut self, timeout_us: u32) {
+        self.inner.rx_discard_timeout_us = timeout_us;
+    }
+
+    /// The number of RX overruns recovered from by [`Chip::recover_rx_overrun`].
+    #[must_use]
+    pub fn rx_overruns(&self) -> u32 {
+        self.inner.rx_overruns
+    }
+
+    /// Program wake-up frame `slot` (0-3) to match frames whose first 64 bytes agree with
+    /// `pattern` wherever `mask` is set (bit `n` of `mask` covers byte `n`), and enable it
+    /// (`WFCR::wfXe`). The CRC the chip compares against is computed here from the masked
+    /// bytes.
+    ///
+    /// A match sets `ISR::rxwfdis` ([`InterruptStatus::wakeup_frame_detected`]). Returns
+    /// [`Error::InvalidWakeupFrameSlot`] for a slot above 3.
+    pub async fn configure_wakeup_frame(
+        &mut self,
+        slot: u8,
+        pattern: &[u8; 64],
+        mask: u64,
+    ) -> Result<(), Error> {
+        if slot > 3 {
+            return Err(Error::InvalidWakeupFrameSlot { slot });
+        }
+        let crc = ethernet_crc32(
+            pattern
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| mask & (1 << i) != 0)
+                .map(|(_, b)| *b),
+        );
+        // WFxCRC0, WFxCRC1, then WFxBM0-3, at 0x30 + 0x10 * x
+        let base = 0x30 + 0x10 * slot;
+        let words = [
+            crc as u16,
+            (crc >> 16) as u16,
+            mask as u16,
+            (mask >> 16) as u16,
+            (mask >> 32) as u16,
+            (mask >> 48) as u16,
+        ];
+        for (i, word) in words.into_iter().enumerate() {
+            self.dev
+                .interface
+                .write_register(base + 2 * i as u8, 16, &word.to_le_bytes())
+                .await?;
+        }
+        self.dev
+            .wfcr()
+            .modify_async(|r| match slot {
+                0 => r.set_wf_0_e(true),
+                1 => r.set_wf_1_e(true),
+                2 => r.set_wf_2_e(true),
+                _ => r.set_wf_3_e(true),
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Read back wake-up frame `slot` (0-3), e.g. to check what
+    /// [`Chip::configure_wakeup_frame`] wrote. Returns [`Error::InvalidWakeupFrameSlot`]
+    /// for a slot above 3.
+    pub async fn read_wakeup_frame_config(
+        &mut self,
+        slot: u8,
+    ) -> Result<WakeupFramePattern, Error> {
+        if slot > 3 {
+            return Err(Error::InvalidWakeupFrameSlot { slot });
+        }
+        // Same layout as in `configure_wakeup_frame`
+        let base = 0x30 + 0x10 * slot;
+        let mut words = [0u16; 6];
+        for (i, word) in words.iter_mut().enumerate() {
+            let mut buf = [0u8; 2];
+            self.dev
+                .interface
+                .read_register(base + 2 * i as u8, 16, &mut buf)
+                .await?;
+            *word = u16::from_le_bytes(buf);
+        }
+        let wfcr = self.dev.wfcr().read_async().await?;
+        Ok(WakeupFramePattern {
+            crc: u32::from(words[0]) | u32::from(words[1]) << 16,
+            mask: words[2..]
+                .iter()
+                .rev()
+                .fold(0, |mask, word| mask << 16 | u64::from(*word)),
+            enabled: match slot {
+                0 => wfcr.wf_0_e(),
+                1 => wfcr.wf_1_e(),
+                2 => wfcr.wf_2_e(),
+                _ => wfcr.wf_3_e(),
+            },
+        })
+    }
+
+    /// Wake on a magic packet for `eui48`, using wake-up frame slot 0.
+    ///
+    /// A magic packet is 6 bytes of 0xFF followed by 16 repetitions of the target MAC
+    /// address. Only the first 64 bytes of a frame can be matched, so this matches the
+    /// sync bytes and the first 7 1/3 repetitions, assuming they follow the Ethernet
+    /// header directly (a raw magic packet, EtherType 0x0842). To wake on magic packets
+    /// for our own MAC address in any encapsulation, e.g. in a UDP broadcast, use the
+    /// dedicated detector (`WFCR::mprxe`) instead.
+    pub async fn configure_magic_packet_for_eui48(&mut self, eui48: [u8; 6]) -> Result<(), Error> {
+        const PAYLOAD_START: usize = 14;
+        let mut pattern = [0u8; 64];
+        pattern[PAYLOAD_START..PAYLOAD_START + 6].fill(0xff);
+        for (i, byte) in pattern[PAYLOAD_START + 6..].iter_mut().enumerate() {
+            *byte = eui48[i % 6];
+        }
+        let mask = !0u64 << PAYLOAD_START;
+        self.configure_wakeup_frame(0, &pattern, mask).await
+    }
+
+    /// Arm wake-on-LAN as described by `config`, and go to sleep.
+    ///
+    /// Interrupts are disabled for the whole transition, so the interrupt pin can't
+    /// fire with the chip half configured. TX is stopped and every interrupt but the
+    /// configured wake-up events is masked, so afterwards INT (and PME) is only asserted
+    /// by network activity: a wake-up frame or magic packet ([`InterruptStatus`]'s
+    /// `wakeup_frame_detected` and `magic_packet_detected`), or energy on the cable
+    /// (`energy_detected`). The receiver stays enabled, as the wake-up detectors sit on
+    /// its path, but received frames are no longer signalled.
+    ///
+    /// With `energy_detect` set the chip is put in energy detect mode, powering the PHY
+    /// down until the cable shows energy, then returning to normal mode by itself.
+    /// Otherwise it stays in normal mode. Either way, run [`Chip::init`] to resume normal
+    /// operation after waking up.
+    pub async fn enable_wakeup_and_sleep(&mut self, config: WakeupConfig) -> Result<(), Error> {
+        self.dev.ier().write_with_zero_async(|_| {}).await?;
+        self.dev.txcr().modify_async(|r| r.set_txe(false)).await?;
+
+        self.dev.wfcr().write_with_zero_async(|_| {}).await?;
+        for (slot, pattern) in config.patterns.iter().enumerate() {
+            if let Some(p) = pattern {
+                self.configure_wakeup_frame(slot as u8, &p.pattern, p.mask)
+                    .await?;
+            }
+        }
+        self.dev
+            .wfcr()
+            .modify_async(|r| r.set_mprxe(config.magic_packet))
+            .await?;
+
+        let wakeup_frames = config.patterns.iter().any(Option::is_some);
+        // Clear any stale wake-up events before unmasking them
+        self.dev
+            .isr()
+            .write_with_zero_async(|r| {
+                r.set_rxwfdis(true);
+                r.set_rxmpdis(true);
+                r.set_edis(true);
+            })
+            .await?;
+        self.dev
+            .pmecr()
+            .write_with_zero_async(|r| {
+                r.set_wake_up_frame_event(true);
+                r.set_magic_packet_event(true);
+                r.set_link_up_event(true);
+                r.set_energy_event(true);
+                r.set_wake_up_frame_pme_enable(wakeup_frames);
+                r.set_magic_packet_pme_enable(config.magic_packet);
+                r.set_energy_detect_pme_enable(config.energy_detect);
+                r.set_auto_wake_up_enable(config.energy_detect);
+                r.set_power_management_mode(if config.energy_detect {
+                    PowerManagementMode::EnergyDetect
+                } else {
+                    PowerManagementMode::Normal
+                });
+            })
+            .await?;
+        self.dev
+            .ier()
+            .write_with_zero_async(|r| {
+                r.set_rxwfdie(wakeup_frames);
+                r.set_rxmpdie(config.magic_packet);
+                r.set_edie(config.energy_detect);
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Wait on the INT pin for a wake-up event, and return which one it was.
+    ///
+    /// This masks every interrupt but the wake-up ones (`IER::rxwfdie`, `IER::rxmpdie`
+    /// and `IER::edie`) and stops TX, like [`Chip::enable_wakeup_and_sleep`] (which should
+    /// be used first to program the wake-up frames and power mode), then waits for `int`
+    /// to go low. Other interrupts that were already pending are ignored. If the pin
+    /// returns an error this falls back to polling `ISR` every millisecond.
+    ///
+    /// The host can suspend itself while the returned future is pending, as long as the
+    /// INT pin can wake it. Run [`Chip::init`] to resume normal operation afterwards.
+    pub async fn wait_wakeup_event(&mut self, int: &mut impl Wait) -> Result<WakeupEvent, Error> {
+        self.dev
+            .ier()
+            .write_with_zero_async(|r| {
+                r.set_rxwfdie(true);
+                r.set_rxmpdie(true);
+                r.set_edie(true);
+            })
+            .await?;
+        self.dev.txcr().modify_async(|r| r.set_txe(false)).await?;
+        loop {
+            if int.wait_for_low().await.is_err() {
+                self.inner.delay.delay_ms(1).await;
+            }
+            let isr = self.dev.isr().read_async().await?;
+            let event = if isr.rxmpdis() {
+                WakeupEvent::MagicPacket
+            } else if isr.rxwfdis() {
+                WakeupEvent::WakeupFrame
+            } else if isr.edis() {
+                WakeupEvent::EnergyDetect
+            } else {
+                continue;
+            };
+            self.dev
+                .isr()
+                .write_with_zero_async(|r| {
+                    r.set_rxmpdis(isr.rxmpdis());
+                    r.set_rxwfdis(isr.rxwfdis());
+                    r.set_edis(isr.edis());
+                })
+                .await?;
+            return Ok(event);
+        }
+    }
+
+    /// Set the on-chip bus clock divider (`OBCR::on_chip_bus_clock_divider_selection`).
+    ///
+    /// Writing the reserved setting leaves the bus clock undefined, so
+    /// [`Ocbcds::Reserved`] is rejected with [`Error::ReservedFieldValue`] rather than
+    /// written.
+    pub async fn set_on_chip_bus_clock_divider(&mut self, divider: Ocbcds) -> Result<(), Error> {
+        if divider == Ocbcds::Reserved {
+            return Err(Error::ReservedFieldValue {
+                register: "OBCR",
+                field: "on_chip_bus_clock_divider_selection",
+            });
+        }
+        self.dev
+            .obcr()
+            .modify_async(|r| r.set_on_chip_bus_clock_divider_selection(divider))
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Set the polarity of the INT pin.
+    ///
+    /// INT is always active low on the KSZ8851SNL (the polarity bit in `PMECR` only
+    /// applies to the PME pin), so this returns [`Error::FeatureNotSupported`] for
+    /// `active_low == false`. Almost every MCU can trigger on a falling edge or low level
+    /// instead; failing that, put an inverter (e.g. a single gate 74LVC1G04) between INT
+    /// and the MCU.
+    pub async fn set_interrupt_pin_polarity(&mut self, active_low: bool) -> Result<(), Error> {
+        if !active_low {
+            return Err(Error::FeatureNotSupported);
+        }
+        Ok(())
+    }
+
+    /// Enable or disable the energy detect interrupts (`IER::edie` and `IER::dedie`).
+    ///
+    /// These fire when the PHY sees energy on the cable, e.g. a cable being plugged in,
+    /// and are reported by [`Chip::handle_interrupt`] as
+    /// [`InterruptStatus::energy_detected`] and
+    /// [`InterruptStatus::delayed_energy_detected`]. Ultra low power designs can use this
+    /// (together with wake-on-LAN) to keep the host asleep until there is a network to
+    /// talk to.
+    pub async fn configure_energy_detect(
+        &mut self,
+        enable: bool,
+        delay: EnergyDetectDelay,
+    ) -> Result<(), Error> {
+        if let EnergyDetectDelay::Custom {
+            go_sleep_time,
+            wake_up_time,
+        } = delay
+        {
+            self.dev
+                .gswutr()
+                .write_async(|r| {
+                    r.set_go_sleep_time(go_sleep_time);
+                    r.set_wake_up_time(wake_up_time);
+                })
+                .await?;
+        }
+        self.dev
+            .ier()
+            .modify_async(|r| {
+                r.set_edie(enable);
+                r.set_dedie(enable);
+            })
+            .await?;
+        Ok(())
+    }
+
+    pub async fn set_leds(&mut self, on: bool) -> Result<(), Error> {
+        self.dev
+            .p_1_mbcr()
+            .modify_async(|r| r.set_disable_led(!on))
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Blink the LEDs `count` times, `period_ms` per blink, e.g. to identify a board in a
+    /// rack of identical ones. The chip has no blink rate control, so this toggles
+    /// [`Chip::set_leds`] with the delay, and leaves the LEDs as they were when done.
+    ///
+    /// "On" here means the LEDs show their normal function (see `CGCR::ledsel0`), so with
+    /// no link they may stay dark throughout.
+    pub async fn blink_led_async(&mut self, period_ms: u32, count: u32) -> Result<(), Error> {
+        let was_on = !self.dev.p_1_mbcr().read_async().await?.disable_led();
+        for _ in 0..count {
+            self.set_leds(!was_on).await?;
+            self.inner.delay.delay_ms(period_ms / 2).await;
+            self.set_leds(was_on).await?;
+            self.inner.delay.delay_ms(period_ms - period_ms / 2).await;
+        }
+        Ok(())
+    }
+
+    /// Choose the auto MDI-X algorithm (`P1MBCR::hp_mdix`): HP mode if `enable`, otherwise
+    /// Microchip mode. The two detect crossover cables differently, and some switches
+    /// (e.g. HP/Aruba) only link reliably with one of them.
+    ///
+    /// The new mode is only used from the next auto-negotiation, so pass `restart_an` to
+    /// restart it straight away. This doesn't wait for auto-negotiation to complete.
+    pub async fn configure_hp_mdix(&mut self, enable: bool, restart_an: bool) -> Result<(), Error> {
+        self.dev
+            .p_1_mbcr()
+            .modify_async(|r| {
+                r.set_hp_mdix(enable);
+                r.set_restart_an(restart_an);
+            })
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Set how the PHY picks between straight (MDI) and crossover (MDI-X) wiring, see
+    /// [`MdixMode`]. This covers [`Chip::configure_hp_mdix`] too, without restarting
+    /// auto-negotiation.
+    pub async fn configure_mdix(&mut self, mode: MdixMode) -> Result<(), Error> {
+        self.dev
+            .p_1_mbcr()
+            .modify_async(|r| {
+                let (disable, force, hp) = match mode {
+                    MdixMode::Auto => (false, false, false),
+                    MdixMode::AutoHp => (false, false, true),
+                    MdixMode::ManualMdi => (true, false, false),
+                    MdixMode::ManualMdix => (true, true, false),
+                };
+                r.set_disable_mdix(disable);
+                r.set_force_mdix(force);
+                r.set_hp_mdix(hp);
+            })
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Put the PHY in local loopback (`P1MBCR::local_far_end_loopback`), so transmitted
+    /// frames are received straight back, or take it out again.
+    ///
+    /// Enabling turns auto-negotiation off and forces 100 Mbps full-duplex, then waits for
+    /// the PHY to report the (artificial, there's no link partner) link up, returning
+    /// [`Error::LoopbackLinkTimeout`] if it doesn't. Disabling turns auto-negotiation back
+    /// on and restarts it, without waiting for it to complete. A reset also ends loopback.
+    ///
+    /// See [`Chip::loopback_tx_rx_test`] for the round trip test to run in between.
+    pub async fn configure_local_loopback_test(&mut self, enable: bool) -> Result<(), Error> {
+        self.dev
+            .p_1_mbcr()
+            .modify_async(|r| {
+                r.set_an_enable(!enable);
+                r.set_force_100(enable);
+                r.set_force_full_duplex(enable);
+                r.set_local_far_end_loopback(enable);
+                r.set_restart_an(!enable);
+            })
+            .await?;
+        if !enable {
+            return Ok(());
+        }
+        for _ in 0..LOOPBACK_TIMEOUT_MS {
+            if self.link_good().await? {
+                return Ok(());
+            }
+            self.inner.delay.delay_ms(1).await;
+        }
+        Err(Error::LoopbackLinkTimeout)
+    }
+
+    /// Send `frame` and check it is received back unchanged, with the PHY in local loopback
+    /// (see [`Chip::configure_local_loopback_test`]). This exercises the whole SPI, MAC and
+    /// PHY data path, so is a good first test for new hardware.
+    ///
+    /// The receive filters still apply, so `frame` should be addressed to our MAC address
+    /// or broadcast. Received padding (frames under 60 bytes) is ignored. Returns
+    /// `Ok(false)` if the frame came back different, was flagged as bad, or didn't come
+    /// back within 100ms.
+    pub async fn loopback_tx_rx_test(&mut self, frame: &[u8]) -> Result<bool, Error> {
+        self.tx_with_timeout(frame, TX_COMPLETE_TIMEOUT_MS).await?;
+        // Not `frame.len()`, short frames come back padded to 60 bytes
+        let mut buf = [0u8; MAX_FRAME_SIZE];
+        for _ in 0..LOOPBACK_TIMEOUT_MS {
+            match self.rx(&mut buf).await {
+                Ok(len) => return Ok(len >= frame.len() && buf[..frame.len()] == *frame),
+                Err(Error::RxFrameInvalid) => return Ok(false),
+                Err(Error::RxNoFrameAvailable { .. }) => self.inner.delay.delay_ms(1).await,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(false)
+    }
+
+    /// Set the MAC address used by the chip
+    ///
+    /// This is [`Chip::set_mac_and_restart`] for a plain byte array.
+    pub async fn set_mac(&mut self, mac_addr: [u8; 6]) -> Result<(), Error> {
+        self.set_mac_and_restart(MacAddress(mac_addr)).await
+    }
+
+    /// Set the MAC address used by the chip, with the receiver stopped while the address
+    /// registers are being written (the address filter works off them in real time, so
+    /// frames could otherwise be filtered against a half-written address).
+    ///
+    /// RX is only re-enabled if it was enabled beforehand, also when the write fails (e.g.
+    /// [`Error::MacVerifyFailed`]). The multicast hash table is keyed on destination group
+    /// addresses rather than our own address, so it doesn't need updating.
+    pub async fn set_mac_and_restart(&mut self, mac: MacAddress) -> Result<(), Error> {
+        let rx_enabled = self.dev.rxcr_1().read_async().await?.rxe();
+        if rx_enabled {
+            self.dev.rxcr_1().modify_async(|r| r.set_rxe(false)).await?;
+        }
+        let result = self.write_and_verify_mac(mac).await;
+        // RX comes back on whether or not the write worked, the caller gets the error
+        if rx_enabled {
+            self.dev.rxcr_1().modify_async(|r| r.set_rxe(true)).await?;
+        }
+        result?;
+        self.inner.mac = Some(mac);
+        Ok(())
+    }
+
+    async fn write_and_verify_mac(&mut self, mac: MacAddress) -> Result<(), Error> {
+        self.write_mac(mac.0).await?;
+        if self.inner.config.verify_mac_writes {
+            check_mac_read_back(mac.0, self.get_mac().await?)?;
+        }
+        Ok(())
+    }
+
+    async fn write_mac(&mut self, mac_addr: [u8; 6]) -> Result<(), Error> {
+        let (high, med, low) = mac_registers(mac_addr);
+        self.dev.marh().write_async(|r| *r = high).await?;
+        self.dev.marm().write_async(|r| *r = med).await?;
+        self.dev.marl().write_async(|r| *r = low).await?;
+        Ok(())
+    }
+
+    /// Check the MAC address in the chip is `expected`, e.g. that a write wasn't corrupted
+    /// on the SPI bus. A wrong address silently breaks unicast filtering.
+    pub async fn verify_mac_address(&mut self, expected: MacAddress) -> Result<bool, Error> {
+        Ok(self.get_mac().await? == expected.0)
+    }
+
+    /// Retrieve the MAC address from the chip.
+    ///
+    /// N.B: it doesn't come with one, so at startup this will be zeroed or garbage
+    pub async fn get_mac(&mut self) -> Result<[u8; 6], Error> {
+        let high = self.dev.marh().read_async().await?;
+        let med = self.dev.marm().read_async().await?;
+        let low = self.dev.marl().read_async().await?;
+        Ok(mac_from_registers(high, med, low))
+    }
+
+    /// Read the 32 bit PHY identifier (MII registers 2 and 3, via `PHY1IHR` and `PHY1ILR`),
+    /// OUI and model in the high bits and revision in the low 4. Reads 0x0022_1430 on the
+    /// KSZ8851SNL.
+    pub async fn get_phy_id(&mut self) -> Result<u32, Error> {
+        let high = self.dev.phy_1_ihr().read_async().await?.id_high();
+        let low = self.dev.phy_1_ilr().read_async().await?.id_low();
+        Ok(((high as u32) << 16) | low as u32)
+    }
+
+    /// Read the PHY status, in two register reads.
+    pub async fn phy_status(&mut self) -> Result<PhyStatus, Error> {
+        let mbsr = self.dev.p_1_mbsr().read_async().await?;
+        let p1sr = self.dev.p_1_sr().read_async().await?;
+        let link_up = mbsr.link_status();
+        let (speed, duplex) = link_mode(p1sr);
+        Ok(PhyStatus {
+            link_up,
+            an_complete: mbsr.an_complete(),
+            an_capable: mbsr.an_capable(),
+            speed: link_up.then_some(speed),
+            duplex: link_up.then_some(duplex),
+            mdix_active: !p1sr.mdi_status(),
+            polarity_reversed: p1sr.polarity_reverse(),
+            t4_capable: mbsr.t_4_capable(),
+            x100_full_capable: mbsr.x_100_full_capable(),
+            x100_half_capable: mbsr.x_100_half_capable(),
+            x10_full_capable: mbsr.x_10_full_capable(),
+            x10_half_capable: mbsr.x_10_half_capable(),
+        })
+    }
+
+    /// Is the link status good (i.e. up)
+    #[must_use = "this only reads the link status"]
+    pub async fn link_good(&mut self) -> Result<bool, Error> {
+        Ok(self.dev.p_1_mbsr().read_async().await?.link_status())
+    }
+
+    /// Enable IEEE 802.3x PAUSE frame flow control in both directions, see
+    /// [`Chip::configure_flow_control`].
+    ///
+    /// There is no pause quantum to pass: the chip has no register for it. On a
+    /// half-duplex link this returns [`Error::FlowControlRequiresFullDuplex`].
+    pub async fn configure_pause_frame(&mut self) -> Result<(), Error> {
+        self.configure_flow_control(true).await
+    }
+
+    /// Enable or disable IEEE 802.3x PAUSE frame flow control in both directions.
+    ///
+    /// This sets `RXCR1::rxfce` (honour PAUSE frames from the link partner) and
+    /// `TXCR::txfce` (send PAUSE frames when the RXQ fills past the flow control
+    /// watermarks in `FCLWR`/`FCHWR`/`FCOWR`).
+    ///
+    /// N.B. the KSZ8851SNL has no register for the transmitted pause quantum, so
+    /// the pause time of generated PAUSE frames is fixed by the hardware.
+    ///
+    /// PAUSE frames only exist in full-duplex mode (the datasheet says to leave both bits
+    /// clear in half-duplex), so enabling returns [`Error::FlowControlRequiresFullDuplex`]
+    /// if the link is currently half-duplex. [`Chip::advance`] does this for you when the
+    /// link comes up, following [`InitConfig::flow_control`].
+    pub async fn configure_flow_control(&mut self, enable: bool) -> Result<(), Error> {
+        if enable && !self.dev.p_1_sr().read_async().await?.operation_duplex() {
+            return Err(Error::FlowControlRequiresFullDuplex);
+        }
+        self.dev
+            .rxcr_1()
+            .modify_async(|r| r.set_rxfce(enable))
+            .await?;
+        self.dev
+            .txcr()
+            .modify_async(|r| r.set_txfce(enable))
+            .await?;
+        Ok(())
+    }
+
+    /// Set the RXQ flow control watermarks (`FCLWR`, `FCHWR` and `FCOWR`), as bytes of
+    /// free space left in the RXQ.
+    ///
+    /// With flow control on (see [`Chip::configure_flow_control`]), the chip sends a PAUSE
+    /// frame when free space drops below `high_threshold_bytes`, and releases it once it
+    /// is back above `low_threshold_bytes`. Below `overrun_threshold_bytes` it treats the
+    /// queue as about to overrun.
+    ///
+    /// The watermarks only prevent overruns if the link partner honours PAUSE frames.
+    /// On a half-duplex link there are no PAUSE frames at all, and some partners ignore
+    /// them, so the RXQ can still fill up and overrun ([`InterruptStatus::rx_overrun`]);
+    /// keep it drained, or see [`Chip::configure_rx_overrun_recovery`]. Raising the
+    /// watermarks pauses the partner earlier, leaving more room for frames already on the
+    /// way.
+    ///
+    /// Returns [`Error::InvalidThreshold`] unless `overrun < high < low`, and all three
+    /// fit in the RXQ ([`RX_BUFFER_SIZE`]) and the 12 bit registers.
+    pub async fn configure_rx_overflow_threshold(
+        &mut self,
+        low_threshold_bytes: u16,
+        high_threshold_bytes: u16,
+        overrun_threshold_bytes: u16,
+    ) -> Result<(), Error> {
+        if overrun_threshold_bytes >= high_threshold_bytes
+            || high_threshold_bytes >= low_threshold_bytes
+            || low_threshold_bytes > RX_BUFFER_SIZE.min(MAX_WATERMARK)
+        {
+            return Err(Error::InvalidThreshold);
+        }
+        self.dev
+            .fclwr()
+            .write_with_zero_async(|r| r.set_fclwc(low_threshold_bytes))
+            .await?;
+        self.dev
+            .fchwr()
+            .write_with_zero_async(|r| r.set_fchwc(high_threshold_bytes))
+            .await?;
+        self.dev
+            .fcowr()
+            .write_with_zero_async(|r| r.set_fcowc(overrun_threshold_bytes))
+            .await?;
+        Ok(())
+    }
+
+    // Called on link up, once the duplex is known.
+    async fn apply_flow_control(&mut self, duplex: Duplex) -> Result<(), Error> {
+        self.configure_flow_control(self.inner.config.flow_control && duplex == Duplex::Full)
+            .await
+    }
+
+    /// Pick the RXQ DMA burst length (`RXCR2::srdbl`) for frames of up to `mtu` bytes.
+    ///
+    /// | `mtu`     | Burst         |
+    /// |-----------|---------------|
+    /// | 0-64      | 4 bytes       |
+    /// | 65-128    | 8 bytes       |
+    /// | 129-512   | 16 bytes      |
+    /// | 513-1000  | 32 bytes      |
+    /// | over 1000 | single frame  |
+    ///
+    /// Each burst is a fixed size, so the last one of a frame is usually only partly
+    /// used. Short bursts waste little on small frames; longer ones need fewer
+    /// transactions for big frames. Past 1000 bytes even 32 byte bursts mean over 30
+    /// transactions a frame, so the whole frame is read in one.
+    ///
+    /// Every burst except in single frame mode needs its own RXQ FIFO Read command byte,
+    /// which is for SPI masters that can only DMA a limited length at once. The driver's own
+    /// RX functions ([`Chip::rx`], [`Chip::rx_with_info`] and [`Chip::read_rx_fifo_raw`])
+    /// read each frame in one burst, and return [`Error::RxBurstLengthNotSingleFrame`]
+    /// for anything else; other burst lengths are for your own RXQ reads through
+    /// [`Chip::interface_mut`]. [`Chip::init`] selects single frame bursts.
+    pub async fn configure_srdbl_burst_length_from_mtu(&mut self, mtu: usize) -> Result<(), Error> {
+        let burst = burst_length_for_mtu(mtu);
+        self.dev
+            .rxcr_2()
+            .modify_async(|r| r.set_srdbl(burst))
+            .await?;
+        self.inner.rx_burst_length = burst;
+        Ok(())
+    }
+
+    /// Set how many frames must accumulate in the RXQ before the RX interrupt fires
+    /// (`RXFCTR::rxfct`). `init()` sets this to 1.
+    ///
+    /// 1 gives the lowest latency; 8-16 cuts interrupt overhead a lot for throughput
+    /// oriented applications at 100 Mbps, at the cost of latency when traffic is light.
+    /// Combine with the duration timer (`RXDTTR`) or byte count (`RXDBCTR`) thresholds to
+    /// bound that latency. 0 disables the frame count threshold (`RXQCR::rxfcte`).
+    pub async fn set_rx_frame_count_threshold(&mut self, threshold: u8) -> Result<(), Error> {
+        // RXFCTR shares the register with the read-only frame count (`rxfc`, bits 8-15).
+        // Writes to those bits are ignored, so there's no need to read-modify-write (and
+        // a stale count written back would look misleading in a bus trace anyway).
+        self.dev
+            .rxfctr()
+            .write_with_zero_async(|r| r.set_rxfct(threshold))
+            .await?;
+        self.dev
+            .rxqcr()
+            .modify_async(|r| r.set_rxfcte(threshold > 0))
+            .await?;
+        Ok(())
+    }
+
+    /// Enable the 2-byte IP header offset, see [`Chip::configure_ip_header_offset_words`].
+    /// This is already on after [`Chip::init`], so is only needed to undo turning it off.
+    ///
+    /// # Why
+    /// The Ethernet header is 14 bytes, so a frame stored at a 4-aligned address has its
+    /// IP header at a 2 mod 4 address. Stacks that read the 32 bit IP/TCP header fields as
+    /// words then make unaligned accesses, which fault on cores without hardware support
+    /// for them (Cortex-M0/M0+ and other ARMv6-M, and many small RISC-V cores), and are
+    /// slower everywhere else. Stacks that parse headers a byte at a time (e.g. smoltcp)
+    /// don't care.
+    ///
+    /// # Effect on the RXQ read
+    /// The chip inserts 2 bytes between the byte count and the frame data (see the
+    /// [module docs](self)), counted in `RXFHBCR`. [`Chip::read_rx_fifo_raw`] returns them,
+    /// which puts the IP header of a frame read into a 4-aligned buffer on a 4-byte
+    /// boundary. [`Chip::rx`] reads them into a scratch buffer instead, so for it the
+    /// alignment is up to the buffer: to get an aligned IP header, pass a slice starting
+    /// 2 bytes into a 4-aligned buffer.
+    pub async fn enable_ip_header_two_byte_offset_for_stack_compat(&mut self) -> Result<(), Error> {
+        self.configure_ip_header_offset_words(true).await
+    }
+
+    /// Enable hardware timestamping of received frames.
+    ///
+    /// The KSZ8851SNL has no IEEE 1588 support or timestamp counter of any kind, so
+    /// enabling this returns [`Error::FeatureNotSupported`] (disabling is a no-op). A
+    /// software timestamp, taken when the RX interrupt is serviced, is the best available,
+    /// and is late by the interrupt latency plus however long the frame waited in the RXQ.
+    pub async fn set_rx_timestamp_enable(&mut self, enable: bool) -> Result<(), Error> {
+        if enable {
+            return Err(Error::FeatureNotSupported);
+        }
+        Ok(())
+    }
+
+    /// Enable or disable the 2-byte pad the chip inserts before each received frame
+    /// (`RXQCR::rxiphtoe`), which puts the IP header on a 4-byte boundary in the RX buffer.
+    ///
+    /// Enabled by default, see [`InitConfig::rx_ip_header_offset`]. [`Chip::rx`] reads the
+    /// pad into a scratch buffer, so the frame written to the caller's buffer always
+    /// starts at the Ethernet header.
+    pub async fn configure_ip_header_offset_words(
+        &mut self,
+        two_byte_offset: bool,
+    ) -> Result<(), Error> {
+        self.dev
+            .rxqcr()
+            .modify_async(|r| r.set_rxiphtoe(two_byte_offset))
+            .await?;
+        self.inner.rx_ip_header_offset = two_byte_offset;
+        Ok(())
+    }
+
+    /// The largest frame [`Chip::tx`] accepts, [`MAX_FRAME_SIZE`].
+    #[must_use]
+    pub const fn max_tx_frame_size() -> usize {
+        MAX_FRAME_SIZE
+    }
+
+    /// The largest frame the chip receives, [`MAX_FRAME_SIZE`], for sizing receive
+    /// buffers.
+    #[must_use]
+    pub const fn max_rx_frame_size() -> usize {
+        MAX_FRAME_SIZE
+    }
+
+    /// The largest byte count a received frame can have, [`MAX_RX_FRAME_BYTES`]. Bigger
+    /// ones are rejected with [`Error::RxFrameCorrupt`].
+    #[must_use]
+    pub const fn get_max_rx_frame_byte_count() -> u16 {
+        MAX_RX_FRAME_BYTES
+    }
+
+    /// Check a TX frame of `len` bytes isn't too big to send, returning
+    /// [`Error::TxPacketTooBig`] if it is.
+    pub const fn validate_frame_size(len: usize) -> Result<(), Error> {
+        validate_frame_size(len)
+    }
+
+    /// The free TXQ memory (`TXMIR::txma`), in bytes. A frame takes up its length plus 4
+    /// bytes of header, rounded up to a multiple of 4.
+    pub async fn get_tx_buffer_free_bytes(&mut self) -> Result<u16, Error> {
+        Ok(self.dev.txmir().read_async().await?.txma())
+    }
+
+    /// Check if the chip has space in the tx buffer to tx a packet of len `tx_len`.
+    /// returns true if there's enough space, false if not. If not, also enables the
+    /// chip's memory available interrupt so we're informed when there is space.
+    #[must_use = "check if TX space is available before calling tx()"]
+    pub async fn ready_tx(&mut self, tx_len: usize) -> Result<bool, Error> {
+        Self::validate_frame_size(tx_len)?;
+        let needed = txq_space_needed(tx_len);
+        let available = self.get_tx_buffer_free_bytes().await?;
+        driver_debug!("TXMIR::txma (tx mem avail) = {}", available);
+        if needed > available.into() {
+            // No room in the device's buffer currently
+            self.dev
+                .txntfsr()
+                .write_with_zero_async(|r| r.set_txntfs(needed as u16))
+                .await?;
+            // Not a read-modify-write, as that could write `metfe` back and enqueue the
+            // frame in flight again, so keep auto enqueue as configured
+            self.dev
+                .txqcr()
+                .write_with_zero_async(|r| {
+                    r.set_txqmam(true);
+                    r.set_aetfe(self.inner.tx_auto_enqueue);
+                })
+                .await?;
+            Ok(false)
+        } else {
+            Ok(true)
+        }
+    }
+
+    /// Wait up to `timeout_ms` for space in the TXQ (see [`Chip::ready_tx`]), then TX the
+    /// frame with [`Chip::tx`].
+    ///
+    /// The free space is polled every millisecond. If the memory available interrupt
+    /// armed by `ready_tx` fires meanwhile (`ISR::txsais`), it is acknowledged here. Returns
+    /// [`Error::TxTimeout`] if there still isn't room after `timeout_ms`.
+    pub async fn tx_with_timeout(
+        &mut self,
+        buf: impl AsRef<[u8]>,
+        timeout_ms: u32,
+    ) -> Result<(), Error> {
+        let buf = buf.as_ref();
+        if !self.ready_tx(buf.len()).await? {
+            let needed = txq_space_needed(buf.len());
+            let mut elapsed = 0;
+            loop {
+                let isr = self.dev.isr().read_async().await?;
+                if isr.txsais() {
+                    self.dev
+                        .isr()
+                        .write_with_zero_async(|r| r.set_txsais(true))
+                        .await?;
+                }
+                let available = self.get_tx_buffer_free_bytes().await?;
+                if needed <= available.into() {
+                    break;
+                }
+                if elapsed == timeout_ms {
+                    return Err(Error::TxTimeout {
+                        bytes_needed: needed,
+                        bytes_available: available,
+                    });
+                }
+                self.inner.delay.delay_ms(1).await;
+                elapsed += 1;
+            }
+        }
+        self.tx(buf).await
+    }
+
+    /// Read back the TXQ space last requested by [`Chip::ready_tx`] for the memory
+    /// available interrupt (`TXNTFSR::txntfs`). Mostly useful for diagnostics.
+    pub async fn get_txq_next_frame_size(&mut self) -> Result<u16, Error> {
+        Ok(self.dev.txntfsr().read_async().await?.txntfs())
+    }
+
+    /// Choose whether each transmitted frame raises a TX complete interrupt.
+    ///
+    /// Enabled by default. Disabling it also clears `IER::txie`; applications that send
+    /// lots of small frames back to back can then poll for completion instead (the
+    /// `TXQCR::metfe` bit self-clears once the frame has been sent).
+    pub async fn configure_tx_interrupt_on_completion(
+        &mut self,
+        enable: bool,
+    ) -> Result<(), Error> {
+        self.dev.ier().modify_async(|r| r.set_txie(enable)).await?;
+        self.inner.tx_interrupt_on_completion = enable;
+        Ok(())
+    }
+
+    /// Choose between manual (the default) and automatic enqueueing of TX frames.
+    ///
+    /// With manual enqueue, [`Chip::tx`] writes a frame and then sets `TXQCR::metfe` to
+    /// queue it, one frame at a time. With auto enqueue (`TXQCR::aetfe`) the chip queues
+    /// frames as soon as they are written, so several can be in the TXQ at once.
+    ///
+    /// N.B. auto enqueue has been seen to be unreliable in practice, and we haven't been
+    /// able to tie the problem to particular silicon revisions, so there is no revision
+    /// check (see [`Chip::get_revision_id`]) to lean on here: enabling it is at your own
+    /// risk, and logs a warning. Please report the revision if you find it reliable.
+    /// [`Chip::is_tx_busy`] and [`Chip::wait_tx_complete`] only work with manual enqueue.
+    pub async fn configure_tx_burst(&mut self, enable_auto_enqueue: bool) -> Result<(), Error> {
+        if enable_auto_enqueue {
+            driver_warn!("TX auto-enqueue enabled, this is known to be unreliable");
+        }
+        self.dev
+            .txqcr()
+            .modify_async(|r| {
+                r.set_aetfe(enable_auto_enqueue);
+                r.set_metfe(false);
+            })
+            .await?;
+        self.inner.tx_auto_enqueue = enable_auto_enqueue;
+        Ok(())
+    }
+
+    /// The frame ID that the next call to [`Chip::tx`] will use.
+    #[must_use]
+    pub fn tx_frame_id(&self) -> u8 {
+        self.inner.next_frame_id
+    }
+
+    /// TX the given frame immediately. This assumes that we know there's enough space in
+    /// the chip's tx buffer by calling having called `ready_tx` already.
+    ///
+    /// This returns as soon as the frame is enqueued, not when it has been sent. Completion
+    /// is signalled by the TX interrupt, or see [`Chip::is_tx_busy`] and
+    /// [`Chip::wait_tx_complete`] to poll for it. With manual enqueue, the previous frame
+    /// has to be sent before the next is enqueued, so this may first wait for that (see
+    /// [`Chip::tx_single_frame_guarantee`]).
+    ///
+    /// # Padding
+    /// The TXQ FIFO has to be written in multiples of 4 bytes, so frames whose length
+    /// isn't a multiple of 4 are followed by up to 3 zero bytes on the SPI bus. These
+    /// aren't part of the frame: the byte count written ahead of the frame is the true
+    /// length, so the chip never transmits them. `buf` can therefore be any length, and
+    /// must contain exactly the frame to send (without the CRC, see `TXCR::txce`).
+    pub async fn tx(&mut self, buf: impl AsRef<[u8]>) -> Result<(), Error> {
+        debug_assert!(self.inner.next_frame_id <= MAX_FRAME_ID);
+        self.tx_frame([buf.as_ref(), &[]], self.inner.next_frame_id)
+            .await?;
+        self.advance_frame_id();
+        Ok(())
+    }
+
+    /// TX a frame made of an Ethernet header and an IP packet in separate buffers, as
+    /// one frame.
+    ///
+    /// The TXQ has no offset setting, and any padding written between the two would be
+    /// sent as part of the frame, so the chip can't realign the IP header for you.
+    /// Instead this writes both parts in the same TXQ transfer, so an IP stack that
+    /// needs its packets 4-aligned (e.g. on Cortex-M0) can keep them in their own aligned
+    /// buffer, without copying them after a 14 byte header. Otherwise as [`Chip::tx`].
+    pub async fn tx_with_ip_header_offset(
+        &mut self,
+        ethernet_header: &[u8],
+        ip_payload: &[u8],
+    ) -> Result<(), Error> {
+        debug_assert!(self.inner.next_frame_id <= MAX_FRAME_ID);
+        self.tx_frame([ethernet_header, ip_payload], self.inner.next_frame_id)
+            .await?;
+        self.advance_frame_id();
+        Ok(())
+    }
+
+    fn advance_frame_id(&mut self) {
+        self.inner.next_frame_id = (self.inner.next_frame_id + 1) & MAX_FRAME_ID;
+    }
+
+    /// TX the given frame immediately with a caller-chosen frame ID, e.g. to correlate
+    /// completions reported in `TXSR::txfid` with specific frames.
+    ///
+    /// `frame_id` must fit in 6 bits (`<= 0x3F`). The automatic ID used by [`Chip::tx`] is
+    /// not advanced. IDs must be unique among in-flight frames - with manual enqueue there
+    /// is only ever one, but mixing this with [`Chip::tx`] means picking an ID that doesn't
+    /// collide with [`Chip::tx_frame_id`].
+    ///
+    /// Padding is handled as for [`Chip::tx`].
+    pub async fn tx_with_id(&mut self, buf: impl AsRef<[u8]>, frame_id: u8) -> Result<(), Error> {
+        self.tx_frame([buf.as_ref(), &[]], frame_id).await
+    }
+
+    // TX the frame made up of `parts`, handling collisions.
+    async fn tx_frame(&mut self, parts: [&[u8]; 2], frame_id: u8) -> Result<(), Error> {
+        let len = parts[0].len() + parts[1].len();
+        let mut retries = 0;
+        loop {
+            self.write_tx_register_header(len, frame_id, self.inner.tx_interrupt_on_completion)
+                .await?;
+            self.write_tx_payload_parts(parts).await?;
+
+            if self.inner.collision_mode == CollisionMode::Discard || self.inner.tx_auto_enqueue {
+                return Ok(());
+            }
+            self.wait_tx_complete(TX_COMPLETE_TIMEOUT_MS).await?;
+            let txsr = self.dev.txsr().read_async().await?;
+            if txsr.txfid() != frame_id || !(txsr.txlc() || txsr.txmc()) {
+                return Ok(());
+            }
+            match self.inner.collision_mode {
+                CollisionMode::Retry { max_retries } if retries < max_retries => retries += 1,
+                _ => return Err(Error::TxCollision),
+            }
+        }
+    }
+
+    /// Choose what [`Chip::tx`] and [`Chip::tx_with_id`] do about frames the MAC gave up on
+    /// because of a collision (`TXSR::txlc` late collision, or `TXSR::txmc` when its own
+    /// 16 attempts all collided). Collisions only happen on half-duplex links.
+    ///
+    /// Anything but [`CollisionMode::Discard`] (the default) makes TX wait until the frame
+    /// has been sent to check `TXSR`, so it no longer returns as soon as the frame is
+    /// queued. This only works with manual enqueue (see [`Chip::configure_tx_burst`]);
+    /// with auto enqueue collisions are always discarded.
+    pub fn configure_collision_handling(&mut self, mode: CollisionMode) {
+        self.inner.collision_mode = mode;
+    }
+
+    /// Make sure the next manually enqueued frame is the only one in flight: wait for the
+    /// previous frame's `TXQCR::metfe` to self-clear, which the datasheet requires before
+    /// enqueueing another. Returns [`Error::TxAlreadyInProgress`] if it is still set after
+    /// a second.
+    ///
+    /// Every TX calls this before writing the frame; it costs one register read when the
+    /// previous frame has already gone. Nothing to do with auto enqueue (see
+    /// [`Chip::configure_tx_burst`]).
+    pub async fn tx_single_frame_guarantee(&mut self) -> Result<(), Error> {
+        if self.inner.tx_auto_enqueue {
+            return Ok(());
+        }
+        for _ in 0..TX_COMPLETE_TIMEOUT_MS {
+            if !self.is_tx_busy().await? {
+                return Ok(());
+            }
+            self.inner.delay.delay_ms(1).await;
+        }
+        if self.is_tx_busy().await? {
+            return Err(Error::TxAlreadyInProgress);
+        }
+        Ok(())
+    }
+
+    /// First half of a split-phase TX: start a TXQ DMA transfer and write the frame header
+    /// (opcode, control word and byte count) as its own SPI transaction. Must be followed by
+    /// [`Chip::write_tx_payload`] with exactly `buf_len` bytes.
+    ///
+    /// This is for SPI peripherals or DMA controllers that can only write one contiguous
+    /// buffer per transaction; otherwise [`Chip::tx`] does both halves for you. Interrupts
+    /// are disabled and the chip ignores register accesses until the payload is written.
+    pub async fn write_tx_register_header(
+        &mut self,
+        buf_len: usize,
+        frame_id: u8,
+        interrupt_on_completion: bool,
+    ) -> Result<(), Error> {
+        self.check_initialized()?;
+        if frame_id > MAX_FRAME_ID {
+            return Err(Error::InvalidFrameId { frame_id });
+        }
+        Self::validate_frame_size(buf_len)?;
+        self.tx_single_frame_guarantee().await?;
+        // Disable interrupts
+        let ier = self.dev.ier().read_async().await?;
+        self.dev.ier().write_with_zero_async(|_| {}).await?;
+        self.start_dma().await?;
+
+        let (txc, byte_count) = tx_frame_header(buf_len, frame_id, interrupt_on_completion);
+        let result = self
+            .dev
+            .interface
+            .bus
+            .transaction(&mut [
+                Operation::Write(&[(Opcode::TXWrite as u8) << 6]),
+                Operation::Write(txc.get_inner_buffer()),
+                Operation::Write(&byte_count),
+            ])
+            .await;
+        if let Err(e) = result {
+            self.end_dma().await?;
+            self.dev.ier().write_async(|r| *r = ier).await?;
+            return Err(e.into());
+        }
+        self.inner.pending_tx = Some(PendingTx {
+            ier,
+            len: buf_len,
+            frame_id,
+        });
+        Ok(())
+    }
+
+    /// Second half of a split-phase TX: write the frame started by
+    /// [`Chip::write_tx_register_header`], end the DMA transfer, enqueue the frame (unless
+    /// auto-enqueue is on, see [`Chip::configure_tx_burst`]) and restore interrupts.
+    ///
+    /// `buf` must be the same length as the header's `buf_len`, and is padded as described
+    /// for [`Chip::tx`].
+    pub async fn write_tx_payload(&mut self, buf: &[u8]) -> Result<(), Error> {
+        self.write_tx_payload_parts([buf, &[]]).await
+    }
+
+    // `write_tx_payload` for a frame split into two buffers.
+    async fn write_tx_payload_parts(&mut self, parts: [&[u8]; 2]) -> Result<(), Error> {
+        let Some(pending) = self.inner.pending_tx else {
+            return Err(Error::NoTxInProgress);
+        };
+        let len = parts[0].len() + parts[1].len();
+        if len != pending.len {
+            return Err(Error::TxLengthMismatch {
+                expected: pending.len,
+                actual: len,
+            });
+        }
+        self.inner.pending_tx = None;
+
+        // Zero padding up to the next 4 byte boundary. Not counted in the header's byte
+        // count, so never transmitted.
+        let pad = &[0u8; 3][..pad_len(len)];
+
+        let result = self
+            .dev
+            .interface
+            .bus
+            .transaction(&mut [
+                Operation::Write(parts[0]),
+                Operation::Write(parts[1]),
+                Operation::Write(pad),
+            ])
+            .await;
+        self.end_dma().await?;
+        if let Err(e) = result {
+            self.dev.ier().write_async(|r| *r = pending.ier).await?;
+            return Err(e.into());
+        }
+
+        if !self.inner.tx_auto_enqueue {
+            // Manually enqueue the frame
+            self.dev.txqcr().modify_async(|r| r.set_metfe(true)).await?;
+        }
+        self.inner.last_tx_frame_id = Some(pending.frame_id);
+
+        // Reenable interrupts
+        self.dev.ier().write_async(|r| *r = pending.ier).await?;
+
+        Ok(())
+    }
+
+    /// Pad frames shorter than the 64 byte minimum (`TXCR::txpe`). On by default.
+    ///
+    /// This is for testing only: with padding off, short frames go out as runts that
+    /// the remote end will drop.
+    pub async fn set_tx_auto_pad(&mut self, enable: bool) -> Result<(), Error> {
+        if !enable {
+            driver_warn!("TX padding disabled, frames under 64 bytes will be sent as runts");
+        }
+        self.dev
+            .txcr()
+            .modify_async(|r| r.set_txpe(enable))
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Append the CRC to transmitted frames (`TXCR::txce`). On by default.
+    ///
+    /// This is for testing only: with it off, every frame fails the receiver's CRC check
+    /// unless the CRC is included at the end of the frame passed to [`Chip::tx`].
+    pub async fn set_tx_auto_crc(&mut self, enable: bool) -> Result<(), Error> {
+        if !enable {
+            driver_warn!("TX CRC disabled, frames will fail CRC checks at the receiver");
+        }
+        self.dev
+            .txcr()
+            .modify_async(|r| r.set_txce(enable))
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Set the priority of transmitted frames.
+    ///
+    /// Always returns [`Error::FeatureNotSupported`]: the KSZ8851SNL QMU has a single TXQ
+    /// with no priority queuing (`TXQCR` only has the enqueue controls), unlike the
+    /// switch parts of the family. Frames are sent in the order they are written.
+    pub async fn set_tx_priority(&mut self, _priority: QosPriority) -> Result<(), Error> {
+        Err(Error::FeatureNotSupported)
+    }
+
+    /// Is a manually enqueued frame still waiting to be transmitted (`TXQCR::metfe`,
+    /// which self-clears once the frame has been sent)?
+    #[must_use = "this only reads the TX status"]
+    pub async fn is_tx_busy(&mut self) -> Result<bool, Error> {
+        Ok(self.dev.txqcr().read_async().await?.metfe())
+    }
+
+    /// Poll for the completion of the last manually enqueued frame, without relying on
+    /// the TX interrupt.
+    ///
+    /// Reads `TXQCR::metfe` for [`TxCompletionStatus::in_progress`], then `TXSR` for the
+    /// collision status. While `in_progress` is set the collision status isn't for this
+    /// frame yet, so call this in a loop until it clears for a synchronous wait.
+    pub async fn poll_tx_complete(&mut self) -> Result<TxCompletionStatus, Error> {
+        let in_progress = self.is_tx_busy().await?;
+        let txsr = self.dev.txsr().read_async().await?;
+        Ok(TxCompletionStatus {
+            in_progress,
+            late_collision: txsr.txlc(),
+            max_collision: txsr.txmc(),
+            frame_id: txsr.txfid(),
+        })
+    }
+
+    /// Wait up to `timeout_ms` for the last transmitted frame to be sent, polling
+    /// [`Chip::is_tx_busy`] every millisecond. Returns [`Error::TxCompleteTimeout`] if
+    /// it still hasn't been sent by then.
+    ///
+    /// With debug assertions enabled, this then checks that the frame the chip reports as
+    /// sent (see [`Chip::get_last_tx_frame_id`]) is the one we last wrote, returning
+    /// [`Error::TxFrameIdMismatch`] if not.
+    pub async fn wait_tx_complete(&mut self, timeout_ms: u32) -> Result<(), Error> {
+        for _ in 0..timeout_ms {
+            if !self.is_tx_busy().await? {
+                return self.confirm_tx_frame_id().await;
+            }
+            self.inner.delay.delay_ms(1).await;
+        }
+        if self.is_tx_busy().await? {
+            return Err(Error::TxCompleteTimeout);
+        }
+        self.confirm_tx_frame_id().await
+    }
+
+    /// The frame ID of the last frame transmitted (`TXSR::txfid`), to match completions
+    /// against the IDs given to [`Chip::tx_with_id`] (or [`Chip::tx_frame_id`] before
+    /// [`Chip::tx`]).
+    pub async fn get_last_tx_frame_id(&mut self) -> Result<u8, Error> {
+        Ok(self.dev.txsr().read_async().await?.txfid())
+    }
+
+    async fn confirm_tx_frame_id(&mut self) -> Result<(), Error> {
+        #[cfg(debug_assertions)]
+        if let Some(sent) = self.inner.last_tx_frame_id {
+            let confirmed = self.get_last_tx_frame_id().await?;
+            if confirmed != sent {
+                return Err(Error::TxFrameIdMismatch { sent, confirmed });
+            }
+        }
+        Ok(())
+    }
+
+    // Get the number of RX frames ready to be read from the chip.
+    // N.B. only updated on interrupt - if no interrupts are enabled this doesn't change!
+    pub async fn rx_frames_available(&mut self) -> Result<u8, Error> {
+        Ok(self.dev.rxfctr().read_async().await?.rxfc())
+    }
+
+    // Until `init()` TX and RX are disabled and the queues unconfigured, so a frame
+    // "sent" would just sit in the TXQ, and nothing can be received.
+    fn check_initialized(&self) -> Result<(), Error> {
+        if self.inner.state == ConnectionState::Uninitialized {
+            return Err(Error::NotInitialized);
+        }
+        Ok(())
+    }
+
+    /// Reset the RX frame data pointer (`RXFDPR::rxfp`) to the start of the frame at the
+    /// head of the RXQ.
+    ///
+    /// This must be done *before* setting `RXQCR::sda` to start a DMA read: while DMA is
+    /// active the chip doesn't accept register writes. [`Chip::rx`] does this for you.
+    pub async fn reset_rx_frame_pointer(&mut self) -> Result<(), Error> {
+        self.dev
+            .rxfdpr()
+            .modify_async(|r| r.set_rxfp(0))
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Receive a single frame from the chip.
+    pub async fn rx(&mut self, rx_buf: &mut [u8]) -> Result<usize, Error> {
+        Ok(self.rx_with_info(rx_buf).await?.len)
+    }
+
+    /// Receive frames until `f` returns `false`.
+    ///
+    /// Each frame is passed to `f` along with its [`RxFrameInfo`]. When there are no
+    /// frames waiting this sleeps for 1ms before checking again, and frames the chip
+    /// flagged as bad are dropped rather than reported. Frames are received into an
+    /// internal buffer big enough for a standard (1518 byte) Ethernet frame. Bigger frames
+    /// end the loop with [`Error::RxBufferTooSmall`], see [`Chip::rx_with_info`].
+    pub async fn continuous_rx<F: FnMut(RxFrameInfo, &[u8]) -> bool>(
+        &mut self,
+        mut f: F,
+    ) -> Result<(), Error> {
+        let mut buf = [0u8; 1518];
+        loop {
+            let available = self.rx_frames_available().await?;
+            if available == 0 {
+                self.inner.delay.delay_ms(1).await;
+                continue;
+            }
+            for _ in 0..available {
+                match self.rx_with_info(&mut buf).await {
+                    Ok(info) => {
+                        if !f(info, &buf[..info.len]) {
+                            return Ok(());
+                        }
+                    }
+                    Err(Error::RxNoFrameAvailable { .. }) => {
+                        self.inner.delay.delay_ms(1).await;
+                        break;
+                    }
+                    Err(Error::RxFrameInvalid) => {}
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+    }
+
+    /// Receive a single frame from the chip, along with what the chip told us about it.
+    pub async fn rx_with_info(&mut self, rx_buf: &mut [u8]) -> Result<RxFrameInfo, Error> {
+        self.check_initialized()?;
+        if self.inner.rx_burst_length != SpiRxDataBurstLength::SingleFrame {
+            return Err(Error::RxBurstLengthNotSingleFrame);
+        }
+        // Disable interrupts, and reenable them however the RX ends
+        let ier = self.dev.ier().read_async().await?;
+        self.dev.ier().write_with_zero_async(|_| {}).await?;
+        let result = self.rx_with_info_interrupts_disabled(rx_buf).await;
+        let restored = self.dev.ier().write_async(|r| *r = ier).await;
+        let info = result?;
+        restored?;
+        Ok(info)
+    }
+
+    async fn rx_with_info_interrupts_disabled(
+        &mut self,
+        rx_buf: &mut [u8],
+    ) -> Result<RxFrameInfo, Error> {
+        let frame_status = self.dev.rxfhsr().read_async().await?;
+        let byte_count = self.dev.rxfhbcr().read_async().await?.rxbc();
+        driver_debug!("frame RX, {} bytes, {}", byte_count, frame_status);
+        if !frame_status.rxfv() {
+            // Either there is no frame or it's not done receiving.
+            let frame_count = self.rx_frames_available().await?;
+            return Err(Error::RxNoFrameAvailable {
+                frame_count,
+                byte_count,
+            });
+        }
+        let ip_header_pad_len = ip_header_pad_len(self.inner.rx_ip_header_offset);
+        let frame_len =
+            match check_rx_frame(frame_status, byte_count, ip_header_pad_len, rx_buf.len()) {
+                Ok(frame_len) => frame_len,
+                Err(e @ (Error::RxFrameInvalid | Error::RxFrameCorrupt { .. })) => {
+                    // Frame error - discard
+                    self.rx_discard_frame().await?;
+                    return Err(e);
+                }
+                Err(e) => return Err(e),
+            };
+
+        // The RXQ DMA sequence for a single frame is:
+        // 1. Read the frame status (RXFHSR) and byte count (RXFHBCR) of the frame at the
+        //    head of the RXQ.
+        // 2. Reset the RX frame pointer (RXFDPR::rxfp) to 0, the start of that frame.
+        // 3. Set RXQCR::sda. From now on the chip only accepts RXQ FIFO reads, not register
+        //    accesses.
+        // 4. Read the frame in one transaction (single frame burst, RXCR2::srdbl). With
+        //    RXFDPR::rxfpai set in `init()` the frame pointer advances on every byte read,
+        //    so we never have to rewrite it mid-frame.
+        // 5. Clear RXQCR::sda. With RXQCR::adrfe set the chip then dequeues the frame and
+        //    the next one (if any) becomes the head of the RXQ, ready for step 1 again.
+        // RXQ DMA read layout, following the RXQ FIFO Read command byte:
+        //
+        //   +-------------+--------+------------+------------+---------//--------+-----+-----+
+        //   | dummy       | RXFHSR | RXFHBCR    | IP hdr pad | frame data        | CRC | pad |
+        //   | 4 bytes     | 2      | 2          | 0 or 2     | n                 | 4   | 0-3 |
+        //   +-------------+--------+------------+------------+---------//--------+-----+-----+
+        //                                        \_______________ byte_count _______________/
+        //
+        // The IP header pad is only present when `RXQCR::rxiphtoe` is set, and is counted
+        // in the byte count along with the CRC. The whole read after the header words must
+        // be a multiple of 4 bytes, hence the trailing pad.
+        let trailing_pad_len = pad_len(byte_count.into());
 
-pub(crate) fn reg_cmd(o: Opcode, addr: u8, count: u8) -> [u8; 2] {
-    // The device only supports accessing 4-aligned addresses, with selectable bytes
-    // being read/written ("byte enables").
-    let byte_enable = match (addr & 0b11, count) {
-        (0, 2) => 0b0011,
-        (2, 2) => 0b1100,
-        (_, _) => unimplemented!(),
-    };
-    [
-        ((o as u8) << 6) | (byte_enable << 2) | (addr >> 6),
-        (addr & 0b00111100) << 2,
-    ]
-}
+        let mut status = Rxfhsr::new_zero();
+        let mut bc = Rxfhbcr::new_zero();
+        let ip_header_pad = &mut [0u8; 2][..ip_header_pad_len as usize];
+        let crc = &mut [0u8; 4];
+        let trailing_pad = &mut [0u8; 3][..trailing_pad_len];
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
-#[cfg_attr(feature = "defmt", derive(defmt::Format))]
-pub enum Error {
-    SpiError(ErrorKind),
-    BadChipId {
-        expected_family: u8,
-        actual_family: u8,
-        expected_chip: u8,
-        actual_chip: u8,
-    },
-    FailedBuiltInSelfTest {
-        rx_bist_failed: bool,
-        tx_bist_failed: bool,
-    },
-    TxPacketTooBig {
-        size: usize,
-        max: u16,
-    },
-    RxFrameInvalid,
-    RxNoFrameAvailable,
-}
+        self.rxq_dma_read(&mut [
+            Operation::Write(&[(Opcode::RXRead as u8) << 6]),
+            Operation::Read(&mut [0u8; 4]),
+            Operation::Read(status.get_inner_buffer_mut()),
+            Operation::Read(bc.get_inner_buffer_mut()),
+            Operation::Read(ip_header_pad),
+            Operation::Read(&mut rx_buf[..frame_len as usize]),
+            Operation::Read(crc),
+            Operation::Read(trailing_pad),
+        ])
+        .await?;
 
-impl<SE: spi::Error> From<SE> for Error {
-    fn from(value: SE) -> Self {
-        Self::SpiError(value.kind())
+        driver_debug!("Got frame with CRC {:x}", u32::from_be_bytes(*crc));
+
+        check_rx_dma_header(frame_status, byte_count, status, bc)?;
+        Ok(RxFrameInfo::new(
+            frame_len,
+            frame_status,
+            self.inner.rx_checksums,
+        ))
+    }
+
+    /// Read the frame at the head of the RXQ exactly as the chip sends it, for users with
+    /// their own frame parsing. Returns the number of bytes written to `buf`.
+    ///
+    /// `buf` receives the whole RXQ read (see the [module docs](self)): 4 dummy bytes,
+    /// `RXFHSR`, `RXFHBCR` (both little endian), then the byte count's worth of IP header
+    /// pad, frame data and CRC. The trailing pad isn't written. The frame is dequeued
+    /// afterwards whether or not its status reports an error.
+    ///
+    /// Returns [`Error::RxNoFrameAvailable`] if there is no complete frame waiting.
+    ///
+    /// Returns [`Error::RxBufferTooSmall`] if `buf` is shorter than 8 bytes plus the
+    /// frame's byte count.
+    pub async fn read_rx_fifo_raw(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        self.check_initialized()?;
+        if self.inner.rx_burst_length != SpiRxDataBurstLength::SingleFrame {
+            return Err(Error::RxBurstLengthNotSingleFrame);
+        }
+        // Disable interrupts, and reenable them however the read ends
+        let ier = self.dev.ier().read_async().await?;
+        self.dev.ier().write_with_zero_async(|_| {}).await?;
+        let result = self.read_rx_fifo_raw_interrupts_disabled(buf).await;
+        let restored = self.dev.ier().write_async(|r| *r = ier).await;
+        let len = result?;
+        restored?;
+        Ok(len)
+    }
+
+    async fn read_rx_fifo_raw_interrupts_disabled(
+        &mut self,
+        buf: &mut [u8],
+    ) -> Result<usize, Error> {
+        let frame_status = self.dev.rxfhsr().read_async().await?;
+        let byte_count = self.dev.rxfhbcr().read_async().await?.rxbc();
+        if !frame_status.rxfv() {
+            let frame_count = self.rx_frames_available().await?;
+            return Err(Error::RxNoFrameAvailable {
+                frame_count,
+                byte_count,
+            });
+        }
+        if byte_count > MAX_RX_FRAME_BYTES {
+            self.rx_discard_frame().await?;
+            return Err(Error::RxFrameCorrupt { byte_count });
+        }
+        let len = 8 + usize::from(byte_count);
+        if len > buf.len() {
+            return Err(Error::RxBufferTooSmall {
+                needed: len,
+                provided: buf.len(),
+            });
+        }
+        let trailing_pad = &mut [0u8; 3][..pad_len(byte_count.into())];
+
+        self.rxq_dma_read(&mut [
+            Operation::Write(&[(Opcode::RXRead as u8) << 6]),
+            Operation::Read(&mut buf[..len]),
+            Operation::Read(trailing_pad),
+        ])
+        .await?;
+
+        Ok(len)
+    }
+
+    // Steps 2-5 of the RXQ DMA sequence described in `rx_with_info`, for a read of the
+    // frame at the head of the RXQ. `ops` must start with the RXQ read opcode.
+    async fn rxq_dma_read(&mut self, ops: &mut [Operation<'_, u8>]) -> Result<(), Error> {
+        self.reset_rx_frame_pointer().await?;
+        self.start_dma().await?;
+        let result = self.dev.interface.bus.transaction(ops).await;
+        self.end_dma().await?;
+        result.map_err(Into::into)
     }
 }
-pub struct Chip<SPI: SpiDevice, D: DelayNs> {
+
+/// A blocking version of [`Chip`], for targets without an async executor, on the
+/// [`embedded_hal`] SPI device and delay traits.
+///
+/// This covers the basics: bring-up, the MAC address, the link, and TX/RX of single
+/// frames, which work as their [`Chip`] counterparts and share their register setup and
+/// checks. Everything else needs either [`Chip`] or the register level device,
+/// [`BlockingChip::dev`].
+///
+/// There is no typestate here: before [`BlockingChip::init`] (and after
+/// [`BlockingChip::reset`]) TX and RX are only guarded at runtime, returning
+/// [`Error::NotInitialized`].
+pub struct BlockingChip<SPI: BlockingSpiDevice, D: BlockingDelayNs> {
     delay: D,
     pub dev: Ksz8851snl<Ksz8851snlInterface<SPI>>,
     next_frame_id: u8,
+    dma_active: bool,
+    config: InitConfig,
+    initialized: bool,
 }
 
-impl<SPI: SpiDevice, D: DelayNs> Chip<SPI, D> {
-    /// Create a new driver from the given SPI device `dev`.
+impl<SPI: BlockingSpiDevice, D: BlockingDelayNs> BlockingChip<SPI, D> {
+    /// Create a new driver from the given SPI device `dev`, to be initialized with the
+    /// default [`InitConfig`].
+    #[must_use]
     pub fn new(dev: SPI, delay: D) -> Self {
+        Self::new_with_config(dev, delay, InitConfig::default())
+    }
+
+    /// Create a new driver from the given SPI device `dev`, to be initialized with `config`
+    /// by [`BlockingChip::init`].
+    #[must_use]
+    pub fn new_with_config(dev: SPI, delay: D, config: InitConfig) -> Self {
         Self {
             delay,
             dev: Ksz8851snl::new(Ksz8851snlInterface { bus: dev }),
             next_frame_id: 0,
+            dma_active: false,
+            config,
+            initialized: false,
         }
     }
 
-    /// Initialize the chip.
-    ///
-    /// This:
-    /// - Resets the chip
-    /// - Checks that it is what we think it is
-    /// - Checks selftest registers
-    /// - Configures RX and TX functions
-    /// - Enables RX and TX
-    pub async fn init(&mut self) -> Result<(), Error> {
-        self.dev
-            .grr()
-            .write_async(|grr| grr.set_global_soft_reset(true))
-            .await?;
-        self.delay.delay_ms(10).await;
-        self.dev.grr().write_with_zero_async(|_| {}).await?;
-        self.delay.delay_ms(10).await;
-        let cider = self.dev.cider().read_async().await?;
-        if cider.chip_id() != CHIP_ID_CHIP || cider.family_id() != CHIP_ID_FAMILY {
-            return Err(Error::BadChipId {
-                expected_family: CHIP_ID_FAMILY,
-                actual_family: cider.family_id(),
-                expected_chip: CHIP_ID_CHIP,
-                actual_chip: cider.chip_id(),
-            });
-        }
-        #[cfg(feature = "defmt")]
-        defmt::info!("Found ksz8851snl rev {}", cider.revision_id());
-        let mbir = self.dev.mbir().read_async().await?;
-        if mbir.rxmbfa() || mbir.txmbfa() {
-            return Err(Error::FailedBuiltInSelfTest {
-                rx_bist_failed: mbir.rxmbfa(),
-                tx_bist_failed: mbir.txmbfa(),
-            });
-        }
-
-        self.dev
-            .txfdpr()
-            .modify_async(|r| r.set_txfpai(true))
-            .await?;
-
-        self.dev
-            .txcr()
-            .modify_async(|r| {
-                r.set_tcgicmp(false);
-                r.set_tcgtcp(false);
-                r.set_tcgip(false);
-                r.set_txfce(false);
-                r.set_txpe(true);
-                r.set_txce(true);
-            })
-            .await?;
+    /// Split the driver into the register level device and the delay.
+    #[must_use]
+    pub fn into_parts(self) -> (Ksz8851snl<Ksz8851snlInterface<SPI>>, D) {
+        (self.dev, self.delay)
+    }
 
-        // Configure rx interrupt to be every 10ms at most. TODO: is this sufficient?
-        // self.dev
-        //     .write_register(RXDTTR::zeroed().with_receive_duration_timer_threshold(1000))
-        //     .await
-        //     .unwrap();
+    /// The configuration [`BlockingChip::init`] applies.
+    #[must_use]
+    pub fn config(&self) -> &InitConfig {
+        &self.config
+    }
 
+    /// Perform a global soft reset, returning every register to its default value.
+    pub fn reset(&mut self) -> Result<(), Error> {
+        if self.dma_active {
+            // Registers (including GRR) are inaccessible until DMA is closed
+            self.end_dma()?;
+        }
         self.dev
-            .rxfdpr()
-            .modify_async(|r| r.set_rxfpai(true))
-            .await?;
-        self.dev.rxfctr().modify_async(|r| r.set_rxfct(1)).await?;
-        self.dev
-            .rxqcr()
-            .modify_async(|r| {
-                r.set_rxfcte(true);
-                // r.set_rxdtte(true);
-                r.set_rxiphtoe(true);
-                r.set_adrfe(true);
-            })
-            .await?;
+            .grr()
+            .write(|grr| grr.set_global_soft_reset(true))?;
+        self.delay.delay_ms(10);
+        self.dev.grr().write_with_zero(|_| {})?;
+        self.delay.delay_ms(10);
+        self.initialized = false;
+        Ok(())
+    }
 
-        self.dev
-            .rxcr_1()
-            .modify_async(|r| {
-                // Disable checksum verification
-                r.set_rxudpfcc(false);
-                r.set_rxtcpfcc(false);
-                r.set_rxipfcc(false);
-
-                r.set_rxfce(true);
-                // You need broadcast for ARP!
-                r.set_rxbe(true);
-                r.set_rxue(true);
-            })
-            .await?;
+    /// Initialize the chip with the stored configuration (see [`BlockingChip::config`]),
+    /// see [`BlockingChip::init_with_config`].
+    pub fn init(&mut self) -> Result<(), Error> {
+        let config = self.config;
+        self.init_with_config(&config)
+    }
 
-        self.dev
-            .rxcr_2()
-            .modify_async(|r| {
-                r.set_iufpp(true);
-                r.set_rxiufcez(true);
-                r.set_udplfe(true);
-                r.set_rxicmpfcc(true);
-                r.set_srdbl(SpiRxDataBurstLength::SingleFrame);
-            })
-            .await?;
+    /// Initialize the chip with `config` as [`Chip::init_with_config`] does, and store it
+    /// for later calls to [`BlockingChip::init`].
+    ///
+    /// [`InitConfig::flow_control`] is ignored, as there is no [`Chip::advance`] to enable
+    /// it when the link comes up.
+    pub fn init_with_config(&mut self, config: &InitConfig) -> Result<(), Error> {
+        config.check()?;
+        self.config = *config;
+        self.reset()?;
+        self.check_chip()?;
 
-        self.dev
-            .ier()
-            .modify_async(|r| {
-                r.set_lcie(true);
-                r.set_txsaie(true);
-                r.set_txie(true);
-                r.set_rxie(true);
-                r.set_rxoie(true);
-                r.set_spibeie(true);
-            })
-            .await?;
+        self.dev.txfdpr().modify(|r| r.set_txfpai(true))?;
+        self.dev.txcr().modify(|r| config.apply_txcr(r))?;
 
-        // There are two ways to transmit - auto enqueue and manual enqueue.
-        // Auto enqueue involves setting TXQCR[2] at init time, and means you can (supposedly)
-        // write multiple frames at once. According to errata this doesn't work reliably?
-        // Manual enqueue involves setting TXQCR[0] *after* you've written the frame to transmit.
-        self.dev
-            .txqcr()
-            .modify_async(|r| r.set_aetfe(false))
-            .await?;
+        self.dev.rxfdpr().modify(|r| r.set_rxfpai(true))?;
+        self.dev.rxfctr().write_with_zero(|r| r.set_rxfct(1))?;
+        self.dev.rxqcr().modify(|r| config.apply_rxqcr(r))?;
+        self.dev.rxcr_1().modify(|r| config.apply_rxcr1(r))?;
+        self.dev.rxcr_2().modify(|r| config.apply_rxcr2(r))?;
+        if config.rx_source_address_filter && self.get_mac()? != [0; 6] {
+            self.dev.rxcr_2().modify(|r| r.set_rxsaf(true))?;
+        }
 
-        self.dev.txcr().modify_async(|r| r.set_txe(true)).await?;
+        let ier = config.ier(true);
+        self.dev.ier().write(|r| *r = ier)?;
 
-        self.dev.rxcr_1().modify_async(|r| r.set_rxe(true)).await?;
+        // Manual enqueue, see `Chip::init_with_config`
+        self.dev.txqcr().modify(|r| r.set_aetfe(false))?;
 
+        self.dev.txcr().modify(|r| r.set_txe(true))?;
+        self.dev.rxcr_1().modify(|r| r.set_rxe(true))?;
+        self.initialized = true;
         Ok(())
     }
 
-    pub async fn set_leds(&mut self, on: bool) -> Result<(), Error> {
-        self.dev
-            .p_1_mbcr()
-            .modify_async(|r| r.set_disable_led(!on))
-            .await
-            .map_err(Into::into)
+    fn check_chip(&mut self) -> Result<ChipInfo, Error> {
+        let info = check_chip_id(self.dev.cider().read()?)?;
+        let mut mbir = self.dev.mbir().read()?;
+        for _ in 0..BIST_TIMEOUT_MS {
+            if bist_done(mbir) {
+                break;
+            }
+            self.delay.delay_ms(1);
+            mbir = self.dev.mbir().read()?;
+        }
+        check_bist(mbir)?;
+        Ok(info)
     }
 
-    /// Set the MAC address used by the chip
-    pub async fn set_mac(&mut self, mac_addr: [u8; 6]) -> Result<(), Error> {
-        self.dev
-            .marh()
-            .write_async(|r| {
-                r.set_ma_5(mac_addr[0]);
-                r.set_ma_4(mac_addr[1]);
-            })
-            .await?;
-        self.dev
-            .marm()
-            .write_async(|r| {
-                r.set_ma_3(mac_addr[2]);
-                r.set_ma_2(mac_addr[3]);
-            })
-            .await?;
-        self.dev
-            .marl()
-            .write_async(|r| {
-                r.set_ma_1(mac_addr[4]);
-                r.set_ma_0(mac_addr[5]);
-            })
-            .await?;
+    /// Set the MAC address used by the chip, with the receiver stopped while the address
+    /// registers are being written, see [`Chip::set_mac_and_restart`]. With
+    /// [`InitConfig::verify_mac_writes`] it is read back afterwards, and RX is restarted
+    /// even if that fails.
+    pub fn set_mac(&mut self, mac_addr: [u8; 6]) -> Result<(), Error> {
+        let rx_enabled = self.dev.rxcr_1().read()?.rxe();
+        if rx_enabled {
+            self.dev.rxcr_1().modify(|r| r.set_rxe(false))?;
+        }
+        let result = self.write_and_verify_mac(mac_addr);
+        if rx_enabled {
+            self.dev.rxcr_1().modify(|r| r.set_rxe(true))?;
+        }
+        result
+    }
+
+    fn write_and_verify_mac(&mut self, mac_addr: [u8; 6]) -> Result<(), Error> {
+        let (high, med, low) = mac_registers(mac_addr);
+        self.dev.marh().write(|r| *r = high)?;
+        self.dev.marm().write(|r| *r = med)?;
+        self.dev.marl().write(|r| *r = low)?;
+        if self.config.verify_mac_writes {
+            check_mac_read_back(mac_addr, self.get_mac()?)?;
+        }
         Ok(())
     }
 
     /// Retrieve the MAC address from the chip.
-    ///
-    /// N.B: it doesn't come with one, so at startup this will be zeroed or garbage
-    pub async fn get_mac(&mut self) -> Result<[u8; 6], Error> {
-        let high = self.dev.marh().read_async().await?;
-        let med = self.dev.marm().read_async().await?;
-        let low = self.dev.marl().read_async().await?;
-        Ok([
-            high.ma_5(),
-            high.ma_4(),
-            med.ma_3(),
-            med.ma_2(),
-            low.ma_1(),
-            low.ma_0(),
-        ])
+    pub fn get_mac(&mut self) -> Result<[u8; 6], Error> {
+        let high = self.dev.marh().read()?;
+        let med = self.dev.marm().read()?;
+        let low = self.dev.marl().read()?;
+        Ok(mac_from_registers(high, med, low))
     }
 
-    /// Is the link status good (i.e. up)
-    pub async fn link_good(&mut self) -> Result<bool, Error> {
-        Ok(self.dev.p_1_mbsr().read_async().await?.link_status())
+    /// Whether the PHY reports the link as up (`P1MBSR::link_status`).
+    pub fn link_good(&mut self) -> Result<bool, Error> {
+        Ok(self.dev.p_1_mbsr().read()?.link_status())
     }
 
-    /// Check if the chip has space in the tx buffer to tx a packet of len `tx_len`.
-    /// returns true if there's enough space, false if not. If not, also enables the
-    /// chip's memory available interrupt so we're informed when there is space.
-    pub async fn ready_tx(&mut self, tx_len: usize) -> Result<bool, Error> {
-        if tx_len > 2000 {
-            return Err(Error::TxPacketTooBig {
-                size: tx_len,
-                max: 2000,
-            });
-        }
-        let available = self.dev.txmir().read_async().await?.txma();
-        #[cfg(feature = "defmt")]
-        defmt::debug!("TXMIR::txma (tx mem avail) = {}", available);
-        if (tx_len + 4) > available.into() {
-            // No room in the device's buffer currently
+    pub fn set_leds(&mut self, on: bool) -> Result<(), Error> {
+        self.dev
+            .p_1_mbcr()
+            .modify(|r| r.set_disable_led(!on))
+            .map_err(Into::into)
+    }
+
+    /// Check if the chip has space in the tx buffer to tx a packet of len `tx_len`, as
+    /// [`Chip::ready_tx`].
+    #[must_use = "check if TX space is available before calling tx()"]
+    pub fn ready_tx(&mut self, tx_len: usize) -> Result<bool, Error> {
+        validate_frame_size(tx_len)?;
+        let needed = txq_space_needed(tx_len);
+        let available = self.dev.txmir().read()?.txma();
+        if needed > available.into() {
             self.dev
                 .txntfsr()
-                .write_with_zero_async(|r| r.set_txntfs((tx_len + 4) as u16))
-                .await?;
-            self.dev
-                .txqcr()
-                .write_with_zero_async(|r| r.set_txqmam(true))
-                .await?;
+                .write_with_zero(|r| r.set_txntfs(needed as u16))?;
+            // Not a read-modify-write, as that could write `metfe` back
+            self.dev.txqcr().write_with_zero(|r| r.set_txqmam(true))?;
             Ok(false)
         } else {
             Ok(true)
         }
     }
 
-    /// TX the given frame immediately. This assumes that we know there's enough space in
-    /// the chip's tx buffer by calling having called `ready_tx` already.
-    pub async fn tx(&mut self, buf: &[u8]) -> Result<(), Error> {
-        // Disable interrupts
-        let ier = self.dev.ier().read_async().await?;
-        self.dev.ier().write_with_zero_async(|_| {}).await?;
-        // Enable TXQ write access
-        self.dev.rxqcr().modify_async(|r| r.set_sda(true)).await?;
-
-        let byte_count: [u8; 2] = (buf.len() as u16).to_le_bytes();
-
-        let mut txc = TxCtrlWord::new_zero();
-        txc.set_transmit_interrupt_on_completion(true);
-        txc.set_frame_id(self.next_frame_id);
+    /// TX a frame, as [`Chip::tx`]. This first waits (up to a second) for the previous
+    /// frame to be sent, see [`Chip::tx_single_frame_guarantee`]. The frame raises a TX
+    /// complete interrupt if [`InterruptMask::tx_complete`] is enabled.
+    pub fn tx(&mut self, buf: impl AsRef<[u8]>) -> Result<(), Error> {
+        self.check_initialized()?;
+        let buf = buf.as_ref();
+        validate_frame_size(buf.len())?;
+        let mut busy = self.dev.txqcr().read()?.metfe();
+        for _ in 0..TX_COMPLETE_TIMEOUT_MS {
+            if !busy {
+                break;
+            }
+            self.delay.delay_ms(1);
+            busy = self.dev.txqcr().read()?.metfe();
+        }
+        if busy {
+            return Err(Error::TxAlreadyInProgress);
+        }
 
-        let _pad = (4 - (buf.len() % 4)) % 4;
-        let pad = &mut [0u8; 3][0.._pad];
+        // Disable interrupts
+        let ier = self.dev.ier().read()?;
+        self.dev.ier().write_with_zero(|_| {})?;
+        self.start_dma()?;
 
-        self.dev
-            .interface
-            .bus
-            .transaction(&mut [
-                Operation::Write(&[(Opcode::TXWrite as u8) << 6]),
-                Operation::Write(txc.get_inner_buffer()),
-                Operation::Write(&byte_count),
-                Operation::Write(buf),
-                Operation::Write(pad),
-            ])
-            .await?;
-        if self.next_frame_id == 0x1f {
-            self.next_frame_id = 0;
-        } else {
-            self.next_frame_id += 1;
+        let (txc, byte_count) = tx_frame_header(
+            buf.len(),
+            self.next_frame_id,
+            self.config.interrupts.tx_complete,
+        );
+        let result = self.dev.interface.bus.transaction(&mut [
+            Operation::Write(&[(Opcode::TXWrite as u8) << 6]),
+            Operation::Write(txc.get_inner_buffer()),
+            Operation::Write(&byte_count),
+            Operation::Write(buf),
+            Operation::Write(&[0u8; 3][..pad_len(buf.len())]),
+        ]);
+        self.end_dma()?;
+        if let Err(e) = result {
+            self.dev.ier().write(|r| *r = ier)?;
+            return Err(e.into());
         }
 
-        // Disable TXQ write access
-        self.dev.rxqcr().modify_async(|r| r.set_sda(false)).await?;
-
         // Manually enqueue the frame
-        self.dev.txqcr().modify_async(|r| r.set_metfe(true)).await?;
+        self.dev.txqcr().modify(|r| r.set_metfe(true))?;
+        self.next_frame_id = (self.next_frame_id + 1) & MAX_FRAME_ID;
 
         // Reenable interrupts
-        self.dev.ier().write_async(|r| *r = ier).await?;
-
+        self.dev.ier().write(|r| *r = ier)?;
         Ok(())
     }
 
     // Get the number of RX frames ready to be read from the chip.
     // N.B. only updated on interrupt - if no interrupts are enabled this doesn't change!
-    pub async fn rx_frames_available(&mut self) -> Result<u8, Error> {
-        Ok(self.dev.rxfctr().read_async().await?.rxfc())
+    pub fn rx_frames_available(&mut self) -> Result<u8, Error> {
+        Ok(self.dev.rxfctr().read()?.rxfc())
     }
 
-    /// Receive a single frame from the chip.
-    pub async fn rx(&mut self, rx_buf: &mut [u8]) -> Result<usize, Error> {
-        // Disable interrupts
-        let ier = self.dev.ier().read_async().await?;
-        assert!(!ier.rxie());
-        self.dev.ier().write_with_zero_async(|_| {}).await?;
-
-        let frame_status = self.dev.rxfhsr().read_async().await?;
-        let byte_count = self.dev.rxfhbcr().read_async().await?.rxbc();
-        #[cfg(feature = "defmt")]
-        defmt::debug!("frame RX, {} bytes, {}", byte_count, frame_status);
-        if !frame_status.rxfv() {
-            // Either there is no frame or it's not done receiving.
-            return Err(Error::RxNoFrameAvailable);
-        }
-        if frame_status.rxce()
-            || frame_status.rxrf()
-            || frame_status.rxftl()
-            || frame_status.rxmr()
-            || frame_status.rxudpfcs()
-            || frame_status.rxtcpfcs()
-            || frame_status.rxipfcs()
-            || frame_status.rxicmpfcs()
-        {
-            // Frame error - discard
-            self.dev.rxqcr().modify_async(|r| r.set_rrxef(true)).await?;
-            // We need to wait until this is cleared before trying to rx again
-            while self.dev.rxqcr().read_async().await?.rrxef() {}
-            return Err(Error::RxFrameInvalid);
+    /// Receive a single frame from the chip, as [`Chip::rx`].
+    pub fn rx(&mut self, rx_buf: &mut [u8]) -> Result<usize, Error> {
+        self.check_initialized()?;
+        if self.config.rx_burst_length != SpiRxDataBurstLength::SingleFrame {
+            return Err(Error::RxBurstLengthNotSingleFrame);
         }
-        if usize::from(byte_count) > rx_buf.len() {
-            panic!("RX byte count too big!!!");
-        }
-
-        // Reset the rx frame pointer
-        self.dev.rxfdpr().modify_async(|r| r.set_rxfp(0)).await?;
+        // Disable interrupts, and reenable them however the RX ends
+        let ier = self.dev.ier().read()?;
+        self.dev.ier().write_with_zero(|_| {})?;
+        let result = self.rx_frame(rx_buf);
+        let restored = self.dev.ier().write(|r| *r = ier);
+        let info = result?;
+        restored?;
+        Ok(info.len)
+    }
 
-        // Enable DMA
-        self.dev.rxqcr().modify_async(|r| r.set_sda(true)).await?;
+    // See `Chip::check_initialized`
+    fn check_initialized(&self) -> Result<(), Error> {
+        if !self.initialized {
+            return Err(Error::NotInitialized);
+        }
+        Ok(())
+    }
 
-        // We need to read a multiple of 4 bytes in total - so we may need some padding
-        let pad = (4 - (byte_count % 4)) % 4;
-        let discard = &mut [0u8; 3];
+    // The RXQ DMA sequence described in `Chip::rx_with_info`, with interrupts disabled
+    fn rx_frame(&mut self, rx_buf: &mut [u8]) -> Result<RxFrameInfo, Error> {
+        let frame_status = self.dev.rxfhsr().read()?;
+        let byte_count = self.dev.rxfhbcr().read()?.rxbc();
+        if !frame_status.rxfv() {
+            return Err(Error::RxNoFrameAvailable {
+                frame_count: self.rx_frames_available()?,
+                byte_count,
+            });
+        }
+        let ip_header_pad_len = ip_header_pad_len(self.config.rx_ip_header_offset);
+        let frame_len =
+            match check_rx_frame(frame_status, byte_count, ip_header_pad_len, rx_buf.len()) {
+                Ok(frame_len) => frame_len,
+                Err(e @ (Error::RxFrameInvalid | Error::RxFrameCorrupt { .. })) => {
+                    self.rx_discard_frame()?;
+                    return Err(e);
+                }
+                Err(e) => return Err(e),
+            };
 
         let mut status = Rxfhsr::new_zero();
         let mut bc = Rxfhbcr::new_zero();
-
         let crc = &mut [0u8; 4];
 
-        self.dev
-            .interface
-            .bus
-            .transaction(&mut [
-                Operation::Write(&[(Opcode::RXRead as u8) << 6]),
-                // 4 dummy bytes
-                Operation::Read(&mut [0u8; 4]),
-                // Two status word bytes
-                Operation::Read(status.get_inner_buffer_mut()),
-                // Two byte count bytes
-                Operation::Read(bc.get_inner_buffer_mut()),
-                // Two IP header offset bytes
-                Operation::Read(&mut [0u8; 2]),
-                Operation::Read(&mut rx_buf[0..(byte_count - 4 - 2) as usize]),
-                Operation::Read(crc),
-                Operation::Read(&mut discard[0..pad as usize]),
-            ])
-            .await
-            .unwrap();
-
-        #[cfg(feature = "defmt")]
-        defmt::debug!("Got frame with CRC {:x}", u32::from_be_bytes(*crc));
+        self.dev.rxfdpr().modify(|r| r.set_rxfp(0))?;
+        self.start_dma()?;
+        let result = self.dev.interface.bus.transaction(&mut [
+            Operation::Write(&[(Opcode::RXRead as u8) << 6]),
+            Operation::Read(&mut [0u8; 4]),
+            Operation::Read(status.get_inner_buffer_mut()),
+            Operation::Read(bc.get_inner_buffer_mut()),
+            Operation::Read(&mut [0u8; 2][..ip_header_pad_len as usize]),
+            Operation::Read(&mut rx_buf[..frame_len as usize]),
+            Operation::Read(crc),
+            Operation::Read(&mut [0u8; 3][..pad_len(byte_count.into())]),
+        ]);
+        self.end_dma()?;
+        result?;
 
-        assert_eq!(frame_status, status);
-        assert_eq!(byte_count, bc.rxbc());
-
-        // Disable DMA
-        self.dev.rxqcr().modify_async(|r| r.set_sda(false)).await?;
+        check_rx_dma_header(frame_status, byte_count, status, bc)?;
+        Ok(RxFrameInfo::new(
+            frame_len,
+            frame_status,
+            self.config.rx_checksums(),
+        ))
+    }
+    // See `Chip::rx_discard_frame`, with the default timeout
+    fn rx_discard_frame(&mut self) -> Result<(), Error> {
+        self.dev.rxqcr().modify(|r| r.set_rrxef(true))?;
+        for _ in 0..RX_DISCARD_TIMEOUT_US {
+            if !self.dev.rxqcr().read()?.rrxef() {
+                return Ok(());
+            }
+            self.delay.delay_us(1);
+        }
+        if self.dev.rxqcr().read()?.rrxef() {
+            return Err(Error::RxDiscardTimeout);
+        }
+        Ok(())
+    }
 
-        // Reenable interrupts
-        self.dev.ier().write_async(|r| *r = ier).await?;
+    // See `Chip::start_dma`
+    fn start_dma(&mut self) -> Result<(), Error> {
+        if self.dma_active {
+            return Err(Error::DmaConflict);
+        }
+        self.dev.rxqcr().modify(|r| r.set_sda(true))?;
+        self.dma_active = true;
+        Ok(())
+    }
 
-        Ok((byte_count - 4).into())
+    fn end_dma(&mut self) -> Result<(), Error> {
+        self.dev.rxqcr().modify(|r| r.set_sda(false))?;
+        self.dma_active = false;
+        Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,374 @@
+use super::*;
+use crate::mock::{self, MockSpi, NoDelay, block_on};
+use std::vec::Vec;
+
+#[test]
+fn txq_space() {
+    // (frame length, TXQ bytes): the 4 byte header, then padded to a multiple of 4
+    let cases = [
+        (0, 4),
+        (1, 8),
+        (4, 8),
+        (5, 12),
+        (60, 64),
+        (61, 68),
+        (1514, 1520),
+        (MAX_FRAME_SIZE, MAX_FRAME_SIZE + 4),
+    ];
+    for (len, needed) in cases {
+        assert_eq!(txq_space_needed(len), needed, "{len} bytes");
+    }
+}
+
+#[test]
+fn frame_size_limit() {
+    for len in [0, 60, 1514, MAX_FRAME_SIZE] {
+        assert_eq!(validate_frame_size(len), Ok(()), "{len} bytes");
+    }
+    for len in [MAX_FRAME_SIZE + 1, usize::MAX] {
+        assert_eq!(
+            validate_frame_size(len),
+            Err(Error::TxPacketTooBig {
+                size: len,
+                max: MAX_FRAME_SIZE as u16,
+            })
+        );
+    }
+}
+
+#[test]
+fn burst_length_from_mtu() {
+    use SpiRxDataBurstLength::*;
+    let cases = [
+        (0, X4Bytes),
+        (64, X4Bytes),
+        (65, X8Bytes),
+        (128, X8Bytes),
+        (129, X16Bytes),
+        (512, X16Bytes),
+        (513, X32Bytes),
+        (1000, X32Bytes),
+        (1001, SingleFrame),
+        (1500, SingleFrame),
+    ];
+    for (mtu, burst) in cases {
+        assert_eq!(burst_length_for_mtu(mtu), burst, "MTU {mtu}");
+    }
+}
+
+#[test]
+fn burst_length_bytes() {
+    use SpiRxDataBurstLength::*;
+    let cases = [
+        (X4Bytes, 0, Some(4)),
+        (X8Bytes, 1, Some(8)),
+        (X16Bytes, 2, Some(16)),
+        (X32Bytes, 3, Some(32)),
+        (SingleFrame, 4, None),
+    ];
+    for (burst, raw, bytes) in cases {
+        assert_eq!(burst.raw_value(), raw);
+        assert_eq!(SpiRxDataBurstLength::from(raw), burst);
+        assert_eq!(burst.burst_size_bytes(), bytes);
+        if let Some(bytes) = bytes {
+            assert_eq!(
+                SpiRxDataBurstLength::from_burst_size_bytes(bytes),
+                Some(burst)
+            );
+        }
+    }
+    for raw in 5..=7 {
+        let burst = SpiRxDataBurstLength::from(raw);
+        assert_eq!(burst, Reserved(raw));
+        assert_eq!(burst.raw_value(), raw);
+        assert_eq!(burst.burst_size_bytes(), None);
+    }
+    for bytes in [0, 2, 12, 64, 2000] {
+        assert_eq!(SpiRxDataBurstLength::from_burst_size_bytes(bytes), None);
+    }
+}
+
+fn init_chip() -> Chip<MockSpi, NoDelay> {
+    block_on(Chip::new(MockSpi::new(), NoDelay).init()).unwrap()
+}
+
+#[test]
+fn rx_after_init() {
+    let mut chip = init_chip();
+    let ier = chip.interface().bus.reg(mock::IER);
+    assert_ne!(ier & 1 << 13, 0, "init should enable rxie");
+    chip.interface_mut().bus.queue_rx_frame(0, 2, &[0x55; 60]);
+    let mut buf = [0u8; 1518];
+    assert_eq!(block_on(chip.rx(&mut buf)), Ok(60));
+    assert_eq!(buf[..60], [0x55; 60]);
+    // Interrupts are back as init left them
+    assert_eq!(chip.interface().bus.reg(mock::IER), ier);
+}
+
+#[test]
+fn read_rx_fifo_raw_after_init() {
+    let mut chip = init_chip();
+    let ier = chip.interface().bus.reg(mock::IER);
+    // An odd length, so the read has a trailing pad that mustn't end up in `buf`
+    let frame = [0x55; 61];
+    chip.interface_mut().bus.queue_rx_frame(0, 2, &frame);
+    let byte_count: u16 = 2 + 61 + 4;
+    let mut buf = [0xaa; 1518];
+    let len = block_on(chip.read_rx_fifo_raw(&mut buf)).unwrap();
+    assert_eq!(len, 8 + usize::from(byte_count));
+    assert_eq!(buf[..4], [0; 4]);
+    assert_eq!(buf[4..6], (1u16 << 15).to_le_bytes(), "RXFHSR");
+    assert_eq!(buf[6..8], byte_count.to_le_bytes(), "RXFHBCR");
+    assert_eq!(buf[8..10], [0xee; 2], "IP header pad");
+    assert_eq!(buf[10..71], frame);
+    assert_eq!(buf[71..75], [0xcc; 4], "CRC");
+    assert_eq!(buf[75..], [0xaa; 1518 - 75]);
+    assert_eq!(chip.interface().bus.reg(mock::IER), ier);
+}
+
+#[test]
+fn blocking_rx_after_init() {
+    let mut chip = BlockingChip::new(MockSpi::new(), NoDelay);
+    chip.init().unwrap();
+    let ier = chip.dev.interface.bus.reg(mock::IER);
+    assert_ne!(ier & 1 << 13, 0, "init should enable rxie");
+    chip.dev.interface.bus.queue_rx_frame(0, 2, &[0x55; 60]);
+    let mut buf = [0u8; 1518];
+    assert_eq!(chip.rx(&mut buf), Ok(60));
+    assert_eq!(buf[..60], [0x55; 60]);
+    assert_eq!(chip.dev.interface.bus.reg(mock::IER), ier);
+}
+
+// The frame ID in the control word of each TXQ write
+fn tx_frame_ids(spi: &MockSpi) -> Vec<u8> {
+    spi.txq.iter().map(|tx| tx[0] & MAX_FRAME_ID).collect()
+}
+
+#[test]
+fn frame_id_wraps() {
+    let mut chip = init_chip();
+    for _ in 0..=MAX_FRAME_ID {
+        block_on(chip.tx([0u8; 60])).unwrap();
+    }
+    assert_eq!(chip.tx_frame_id(), 0);
+    block_on(chip.tx([0u8; 60])).unwrap();
+    let ids = tx_frame_ids(&chip.interface().bus);
+    assert!(ids[..64].iter().copied().eq(0..=MAX_FRAME_ID));
+    assert_eq!(ids[64], 0);
+}
+
+#[test]
+fn blocking_frame_id_wraps() {
+    let mut chip = BlockingChip::new(MockSpi::new(), NoDelay);
+    chip.init().unwrap();
+    for _ in 0..=MAX_FRAME_ID + 1 {
+        chip.tx([0u8; 60]).unwrap();
+    }
+    let ids = tx_frame_ids(&chip.dev.interface.bus);
+    assert!(ids[..64].iter().copied().eq(0..=MAX_FRAME_ID));
+    assert_eq!(ids[64], 0);
+}
+
+// (TXMIR::txma, frame length, whether it fits): the frame needs `txq_space_needed` bytes
+const READY_TX_CASES: [(u16, usize, bool); 6] = [
+    (64, 60, true),
+    (63, 60, false),
+    (0, 0, false),
+    (4, 0, true),
+    (1519, 1514, false),
+    (6 * 1024, 1514, true),
+];
+
+// Not fitting arms the memory available interrupt for the space needed
+fn check_ready_tx(spi: &MockSpi, len: usize, ready: bool) {
+    if ready {
+        assert_eq!(spi.reg(mock::TXNTFSR), 0, "{len} bytes");
+    } else {
+        assert_eq!(
+            usize::from(spi.reg(mock::TXNTFSR)),
+            txq_space_needed(len),
+            "{len} bytes"
+        );
+        assert_ne!(spi.reg(mock::TXQCR) & 1 << 1, 0, "{len} bytes: txqmam");
+    }
+}
+
+#[test]
+fn ready_tx() {
+    for (txma, len, ready) in READY_TX_CASES {
+        let mut chip = init_chip();
+        chip.interface_mut().bus.set_reg(mock::TXMIR, txma);
+        assert_eq!(block_on(chip.get_tx_buffer_free_bytes()), Ok(txma));
+        assert_eq!(
+            block_on(chip.ready_tx(len)),
+            Ok(ready),
+            "{txma} free, {len} bytes"
+        );
+        check_ready_tx(&chip.interface().bus, len, ready);
+    }
+}
+
+#[test]
+fn blocking_ready_tx() {
+    for (txma, len, ready) in READY_TX_CASES {
+        let mut chip = BlockingChip::new(MockSpi::new(), NoDelay);
+        chip.init().unwrap();
+        chip.dev.interface.bus.set_reg(mock::TXMIR, txma);
+        assert_eq!(chip.ready_tx(len), Ok(ready), "{txma} free, {len} bytes");
+        check_ready_tx(&chip.dev.interface.bus, len, ready);
+    }
+}
+
+#[test]
+fn icmp_checksum_offload() {
+    const TCGICMP: u16 = 1 << 8;
+    const RXICMPFCC: u16 = 1 << 1;
+    for (tx_enable, rx_enable) in [(false, false), (true, false), (false, true), (true, true)] {
+        let mut chip = init_chip();
+        let txcr = chip.interface().bus.reg(mock::TXCR);
+        let rxcr2 = chip.interface().bus.reg(mock::RXCR2);
+        block_on(chip.configure_icmp_checksum_offload(tx_enable, rx_enable)).unwrap();
+
+        let spi = &chip.interface().bus;
+        let case = (tx_enable, rx_enable);
+        assert_eq!(spi.reg(mock::TXCR) & TCGICMP != 0, tx_enable, "{case:?}");
+        assert_eq!(spi.reg(mock::RXCR2) & RXICMPFCC != 0, rx_enable, "{case:?}");
+        // Nothing else in either register changes
+        assert_eq!(spi.reg(mock::TXCR) & !TCGICMP, txcr & !TCGICMP, "{case:?}");
+        assert_eq!(
+            spi.reg(mock::RXCR2) & !RXICMPFCC,
+            rxcr2 & !RXICMPFCC,
+            "{case:?}"
+        );
+    }
+}
+
+const RXIPHTOE: u16 = 1 << 9;
+
+#[test]
+fn rx_ip_header_offset() {
+    let frame: Vec<u8> = (0..61).collect();
+    for two_byte_offset in [true, false] {
+        let mut chip = init_chip();
+        block_on(chip.configure_ip_header_offset_words(two_byte_offset)).unwrap();
+        let spi = &mut chip.interface_mut().bus;
+        assert_eq!(spi.reg(mock::RXQCR) & RXIPHTOE != 0, two_byte_offset);
+        spi.queue_rx_frame(0, if two_byte_offset { 2 } else { 0 }, &frame);
+
+        let mut buf = [0u8; 1518];
+        assert_eq!(
+            block_on(chip.rx(&mut buf)),
+            Ok(frame.len()),
+            "{two_byte_offset}"
+        );
+        assert_eq!(buf[..frame.len()], frame, "{two_byte_offset}");
+    }
+}
+
+#[test]
+fn blocking_rx_ip_header_offset() {
+    let frame: Vec<u8> = (0..61).collect();
+    for two_byte_offset in [true, false] {
+        let config = InitConfig {
+            rx_ip_header_offset: two_byte_offset,
+            ..InitConfig::new()
+        };
+        let mut chip = BlockingChip::new_with_config(MockSpi::new(), NoDelay, config);
+        chip.init().unwrap();
+        let spi = &mut chip.dev.interface.bus;
+        assert_eq!(spi.reg(mock::RXQCR) & RXIPHTOE != 0, two_byte_offset);
+        spi.queue_rx_frame(0, if two_byte_offset { 2 } else { 0 }, &frame);
+
+        let mut buf = [0u8; 1518];
+        assert_eq!(chip.rx(&mut buf), Ok(frame.len()), "{two_byte_offset}");
+        assert_eq!(buf[..frame.len()], frame, "{two_byte_offset}");
+    }
+}
+
+#[test]
+fn check_health_before_init() {
+    let mut chip = Chip::new(MockSpi::new(), NoDelay);
+    let report = block_on(chip.check_health()).unwrap();
+    assert_eq!(
+        report,
+        HealthReport {
+            link_up: false,
+            bist_ok: true,
+            pending_rx_frames: 0,
+            tx_buffer_available: 6 * 1024,
+            spi_error_detected: false,
+            rx_overrun_detected: false,
+            revision_id: 1,
+        }
+    );
+    // Nothing was written, the chip is left as it was
+    assert!(chip.interface().bus.writes.is_empty());
+}
+
+#[test]
+fn tx_rx_after_reset() {
+    let mut chip = init_chip();
+    block_on(chip.reset()).unwrap();
+    let mut buf = [0u8; 1518];
+    assert_eq!(block_on(chip.tx([0u8; 60])), Err(Error::NotInitialized));
+    assert_eq!(block_on(chip.rx(&mut buf)), Err(Error::NotInitialized));
+    block_on(chip.init()).unwrap();
+    assert_eq!(block_on(chip.tx([0u8; 60])), Ok(()));
+}
+
+#[test]
+fn blocking_tx_rx_before_init() {
+    let mut chip = BlockingChip::new(MockSpi::new(), NoDelay);
+    let mut buf = [0u8; 1518];
+    assert_eq!(chip.tx([0u8; 60]), Err(Error::NotInitialized));
+    assert_eq!(chip.rx(&mut buf), Err(Error::NotInitialized));
+    chip.init().unwrap();
+    assert_eq!(chip.tx([0u8; 60]), Ok(()));
+    chip.reset().unwrap();
+    assert_eq!(chip.tx([0u8; 60]), Err(Error::NotInitialized));
+    assert_eq!(chip.dev.interface.bus.txq.len(), 1);
+}
+
+#[test]
+fn mac_verify_failure_restarts_rx() {
+    let config = InitConfig {
+        verify_mac_writes: true,
+        ..InitConfig::new()
+    };
+    let mut chip = block_on(Chip::new_with_config(MockSpi::new(), NoDelay, config).init()).unwrap();
+    chip.interface_mut().bus.stuck.push(mock::MARL);
+    assert_eq!(
+        block_on(chip.set_mac([0x02, 0, 0, 0, 0, 0x01])),
+        Err(Error::MacVerifyFailed)
+    );
+    assert_ne!(chip.interface().bus.reg(mock::RXCR1) & 1, 0, "rxe");
+}
+
+#[test]
+fn blocking_mac_verify_failure_restarts_rx() {
+    let config = InitConfig {
+        verify_mac_writes: true,
+        ..InitConfig::new()
+    };
+    let mut chip = BlockingChip::new_with_config(MockSpi::new(), NoDelay, config);
+    chip.init().unwrap();
+    chip.dev.interface.bus.stuck.push(mock::MARL);
+    assert_eq!(
+        chip.set_mac([0x02, 0, 0, 0, 0, 0x01]),
+        Err(Error::MacVerifyFailed)
+    );
+    assert_ne!(chip.dev.interface.bus.reg(mock::RXCR1) & 1, 0, "rxe");
+}
+
+#[test]
+fn rx_overrun_reported_with_other_interrupts() {
+    let mut chip = init_chip();
+    // rxis and rxois
+    chip.interface_mut()
+        .bus
+        .set_reg(mock::ISR, 1 << 13 | 1 << 11);
+    let status = block_on(chip.handle_interrupt()).unwrap();
+    assert!(status.rx_overrun);
+    assert!(status.rx_ready);
+    assert_eq!(chip.rx_overruns(), 0, "recovery is off by default");
+}
@@ -1,4 +1,79 @@
-//! Low level register descriptions and driver for the KSZ8851SNL SPI Ethernet controller"
+//! Low level register descriptions and driver for the KSZ8851SNL SPI Ethernet controller
+//!
+//! The driver ([`driver::Chip`]) works with any [`embedded_hal_async`] SPI device and
+//! delay. The KSZ8851SNL uses SPI mode 0 at up to 40MHz, and has an active low interrupt
+//! pin. With embassy, the SPI device is typically an `embassy_embedded_hal` shared bus
+//! `SpiDevice` (or `embedded_hal_bus::spi::ExclusiveDevice`), the delay is
+//! `embassy_time::Delay` and the interrupt pin an `ExtiInput`.
+//!
+//! # Features
+//! - `defmt` (default): `defmt::Format` for the driver and register types, and warnings.
+//! - `defmt-debug`: also log the chip revision, TX memory and every frame received.
+//! - `defmt-trace`: also log every register access.
+//! - `std`: implement `core::error::Error` for [`driver::Error`].
+//! - `serde`: implement `serde::Deserialize` for [`driver::InitConfig`]. Fields left out
+//!   take their defaults, apart from the [`driver::InterruptMask`] bits, which are off.
+//!
+//! The usual `DEFMT_LOG` filtering still applies on top of these.
+//!
+//! A minimal setup, transmitting a frame and receiving on interrupt:
+//! ```
+//! use embedded_hal_async::{delay::DelayNs, digital::Wait, spi::SpiDevice};
+//! use ksz8851snl::prelude::*;
+//!
+//! async fn run<SPI: SpiDevice, D: DelayNs, INT: Wait>(
+//!     spi: SPI,
+//!     delay: D,
+//!     mut int: INT,
+//! ) -> Result<(), Error> {
+//!     // Only the initialized chip returned by `init` can send and receive
+//!     let mut chip = Chip::new(spi, delay).init().await?;
+//!     // The chip doesn't come with a MAC address, so you need to provide one
+//!     chip.set_mac([0x02, 0x00, 0x00, 0x00, 0x00, 0x01]).await?;
+//!
+//!     // Broadcast frame: destination, source, ethertype, payload
+//!     let mut frame = [0u8; 60];
+//!     frame[0..6].copy_from_slice(&[0xff; 6]);
+//!     frame[6..12].copy_from_slice(&chip.get_mac().await?);
+//!     frame[12..14].copy_from_slice(&[0x88, 0xb5]);
+//!     if chip.ready_tx(frame.len()).await? {
+//!         chip.tx(&frame).await?;
+//!     }
+//!
+//!     let mut rx_buf = [0u8; 1518];
+//!     loop {
+//!         int.wait_for_low().await.ok();
+//!         let status = chip.handle_interrupt().await?;
+//!         if status.link_changed {
+//!             let _up = chip.link_good().await?;
+//!         }
+//!         if status.rx_ready {
+//!             for _ in 0..chip.rx_frames_available().await? {
+//!                 match chip.rx(&mut rx_buf).await {
+//!                     Ok(len) => {
+//!                         // Hand &rx_buf[..len] to your network stack here
+//!                     }
+//!                     // The chip flagged the frame as bad, and it has been dropped
+//!                     Err(Error::RxFrameInvalid) => {}
+//!                     Err(e) => return Err(e),
+//!                 }
+//!             }
+//!         }
+//!     }
+//! }
+//! ```
 #![no_std]
+#[cfg(any(feature = "std", test))]
+extern crate std;
+#[macro_use]
+mod log_macros;
+#[cfg(feature = "serde")]
+mod de;
 pub mod device;
 pub mod driver;
+#[cfg(test)]
+mod mock;
+pub mod pool;
+pub mod prelude;
+pub mod raw;
+pub mod registers;
@@ -0,0 +1,33 @@
+//! Logging macros, so that call sites don't each need a `#[cfg]` for their log level.
+//!
+//! Each takes a `defmt` format string and arguments, and compiles to nothing unless the
+//! matching feature is enabled: `driver_error!` and `driver_warn!` with `defmt`, `driver_debug!` with
+//! `defmt-debug` and `driver_trace!` with `defmt-trace`.
+
+macro_rules! driver_error {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "defmt")]
+        defmt::error!($($arg)*);
+    };
+}
+
+macro_rules! driver_warn {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "defmt")]
+        defmt::warn!($($arg)*);
+    };
+}
+
+macro_rules! driver_debug {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "defmt-debug")]
+        defmt::debug!($($arg)*);
+    };
+}
+
+macro_rules! driver_trace {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "defmt-trace")]
+        defmt::trace!($($arg)*);
+    };
+}
@@ -0,0 +1,203 @@
+//! A simulated KSZ8851SNL on the SPI bus, for testing the driver without hardware.
+//!
+//! [`MockSpi`] decodes the register commands from [`crate::raw::reg_cmd`] into a register
+//! file, serves [`MockSpi::rxq`] to RXQ reads and records every TXQ write. Both the
+//! blocking and async `SpiDevice` traits are implemented, with [`block_on`] to run the
+//! async driver. Only the chip behaviour the driver relies on is simulated: the ID and
+//! BIST registers read as a healthy chip, and the self-clearing `RXQCR::rrxef` and
+//! `TXQCR::metfe` clear straight away.
+use crate::raw::Opcode;
+use core::convert::Infallible;
+use core::future::Future;
+use core::pin::pin;
+use core::task::{Context, Poll, Waker};
+use embedded_hal::spi::{ErrorType, Operation};
+use std::vec::Vec;
+
+pub const MARL: u8 = 0x10;
+pub const MBIR: u8 = 0x24;
+pub const TXCR: u8 = 0x70;
+pub const RXCR1: u8 = 0x74;
+pub const RXCR2: u8 = 0x76;
+pub const TXMIR: u8 = 0x78;
+pub const RXFHSR: u8 = 0x7c;
+pub const RXFHBCR: u8 = 0x7e;
+pub const TXQCR: u8 = 0x80;
+pub const RXQCR: u8 = 0x82;
+pub const IER: u8 = 0x90;
+pub const ISR: u8 = 0x92;
+pub const TXNTFSR: u8 = 0x9e;
+pub const CIDER: u8 = 0xc0;
+
+pub struct MockSpi {
+    regs: [u16; 128],
+    /// The bytes RXQ reads return, following the opcode
+    pub rxq: Vec<u8>,
+    /// The bytes of each TXQ write, following the opcode
+    pub txq: Vec<Vec<u8>>,
+    /// Every register write, as (address, value)
+    pub writes: Vec<(u8, u16)>,
+    /// Registers whose writes are lost, as with a stuck bus
+    pub stuck: Vec<u8>,
+}
+
+impl MockSpi {
+    pub fn new() -> Self {
+        let mut spi = Self {
+            regs: [0; 128],
+            rxq: Vec::new(),
+            txq: Vec::new(),
+            writes: Vec::new(),
+            stuck: Vec::new(),
+        };
+        // Family 0x88, chip 0x7, revision 1
+        spi.set_reg(CIDER, 0x8872);
+        // TX and RX BIST finished and passed
+        spi.set_reg(MBIR, 1 << 12 | 1 << 4);
+        spi.set_reg(TXMIR, 6 * 1024);
+        spi
+    }
+
+    pub fn reg(&self, addr: u8) -> u16 {
+        self.regs[usize::from(addr / 2)]
+    }
+
+    pub fn set_reg(&mut self, addr: u8, value: u16) {
+        self.regs[usize::from(addr / 2)] = value;
+    }
+
+    /// Queue a frame for the next RXQ read, as the chip sends it: `RXFHSR` (valid, plus
+    /// `status`) and the byte count (`pad`, the frame and its CRC) in the registers and at
+    /// the start of the read, then the IP header pad, frame, CRC and trailing pad.
+    pub fn queue_rx_frame(&mut self, status: u16, pad: usize, frame: &[u8]) {
+        let status = status | 1 << 15;
+        let byte_count = (pad + frame.len() + 4) as u16;
+        self.set_reg(RXFHSR, status);
+        self.set_reg(RXFHBCR, byte_count);
+        self.rxq.clear();
+        self.rxq.extend_from_slice(&[0; 4]);
+        self.rxq.extend_from_slice(&status.to_le_bytes());
+        self.rxq.extend_from_slice(&byte_count.to_le_bytes());
+        self.rxq.extend(core::iter::repeat_n(0xee, pad));
+        self.rxq.extend_from_slice(frame);
+        self.rxq.extend_from_slice(&[0xcc; 4]);
+        self.rxq
+            .extend(core::iter::repeat_n(0, (4 - byte_count as usize % 4) % 4));
+    }
+
+    fn transaction(&mut self, ops: &mut [Operation<'_, u8>]) {
+        // Register accesses are always a 2 byte command then 2 bytes of data, FIFO
+        // accesses start with a 1 byte opcode or carry on from the last one
+        if let [Operation::Write(cmd), data] = ops
+            && cmd.len() == 2
+            && cmd[0] >> 7 == 0
+        {
+            // Every access the driver makes is 16 bits, at the A7:A2 address and the
+            // upper half if the byte enables say so
+            let addr = (cmd[0] & 0b11) << 6 | cmd[1] >> 2 | (cmd[0] >> 4 & 0b10);
+            match data {
+                Operation::Read(buf) => buf.copy_from_slice(&self.reg(addr).to_le_bytes()),
+                Operation::Write(buf) => {
+                    let value = u16::from_le_bytes([buf[0], buf[1]]);
+                    self.write_reg(addr, value);
+                }
+                _ => unimplemented!(),
+            }
+            return;
+        }
+        let mut ops = ops.iter_mut();
+        if let Some(Operation::Write([opcode])) = ops.as_slice().first() {
+            if opcode >> 6 == Opcode::TXWrite as u8 {
+                self.txq.push(Vec::new());
+            }
+            ops.next();
+        }
+        let mut rxq = self.rxq.iter().copied();
+        for op in ops {
+            match op {
+                Operation::Read(buf) => {
+                    for byte in buf.iter_mut() {
+                        *byte = rxq.next().expect("RXQ read past the queued frame");
+                    }
+                }
+                Operation::Write(buf) => self.txq.last_mut().unwrap().extend_from_slice(buf),
+                _ => unimplemented!(),
+            }
+        }
+    }
+
+    fn write_reg(&mut self, addr: u8, mut value: u16) {
+        self.writes.push((addr, value));
+        if self.stuck.contains(&addr) {
+            return;
+        }
+        match addr {
+            // A release (`rrxef`) dequeues the frame, and the bit clears once done
+            RXQCR if value & 1 != 0 => {
+                self.set_reg(RXFHSR, 0);
+                value &= !1;
+            }
+            // The frame (`metfe`) is sent straight away
+            TXQCR => value &= !1,
+            _ => {}
+        }
+        self.set_reg(addr, value);
+    }
+}
+
+impl ErrorType for MockSpi {
+    type Error = Infallible;
+}
+
+impl embedded_hal::spi::SpiDevice for MockSpi {
+    fn transaction(&mut self, ops: &mut [Operation<'_, u8>]) -> Result<(), Infallible> {
+        MockSpi::transaction(self, ops);
+        Ok(())
+    }
+}
+
+impl embedded_hal_async::spi::SpiDevice for MockSpi {
+    async fn transaction(&mut self, ops: &mut [Operation<'_, u8>]) -> Result<(), Infallible> {
+        MockSpi::transaction(self, ops);
+        Ok(())
+    }
+}
+
+/// Throws away the driver's log output, so the defmt calls link in tests.
+#[cfg(feature = "defmt")]
+#[defmt::global_logger]
+struct NoLogger;
+
+#[cfg(feature = "defmt")]
+unsafe impl defmt::Logger for NoLogger {
+    fn acquire() {}
+    unsafe fn flush() {}
+    unsafe fn release() {}
+    unsafe fn write(_bytes: &[u8]) {}
+}
+
+#[cfg(feature = "defmt")]
+defmt::timestamp!("");
+
+/// A delay that returns straight away.
+pub struct NoDelay;
+
+impl embedded_hal::delay::DelayNs for NoDelay {
+    fn delay_ns(&mut self, _ns: u32) {}
+}
+
+impl embedded_hal_async::delay::DelayNs for NoDelay {
+    async fn delay_ns(&mut self, _ns: u32) {}
+}
+
+/// Run `future` to completion. Nothing the mock does ever has to wait, so it is just
+/// polled until ready.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let mut cx = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
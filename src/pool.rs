@@ -0,0 +1,174 @@
+//! Fixed-size frame buffer pool for `no_std` environments without an allocator.
+//!
+//! The pool is sized entirely at compile time, so needs no allocator, and can be shared
+//! between TX queuing and RX storage. The free list is made of plain `Cell`s, so the
+//! pool isn't `Sync` and can't be a `static`: keep it with the task doing the networking.
+use core::cell::{Cell, UnsafeCell};
+use core::ops::{Deref, DerefMut};
+
+/// A pool of `N` frame buffers, each `FRAME_SIZE` bytes long.
+pub struct FramePool<const N: usize, const FRAME_SIZE: usize> {
+    buffers: [UnsafeCell<[u8; FRAME_SIZE]>; N],
+    // Free list, threaded through the slot indices. `None` marks the end of the list.
+    next_free: [Cell<Option<usize>>; N],
+    head: Cell<Option<usize>>,
+}
+
+impl<const N: usize, const FRAME_SIZE: usize> FramePool<N, FRAME_SIZE> {
+    /// Create a new pool with all buffers free and zeroed.
+    pub const fn new() -> Self {
+        let mut next_free = [const { Cell::new(None) }; N];
+        let mut i = 0;
+        while i + 1 < N {
+            next_free[i] = Cell::new(Some(i + 1));
+            i += 1;
+        }
+        Self {
+            buffers: [const { UnsafeCell::new([0; FRAME_SIZE]) }; N],
+            next_free,
+            head: Cell::new(if N > 0 { Some(0) } else { None }),
+        }
+    }
+
+    /// Take a buffer from the pool, or `None` if all buffers are in use.
+    ///
+    /// The buffer is returned to the pool when the [`FrameBuffer`] is dropped. Its contents
+    /// are whatever the previous user left in it.
+    pub fn alloc(&self) -> Option<FrameBuffer<'_, FRAME_SIZE>> {
+        let index = self.head.get()?;
+        self.head.set(self.next_free[index].take());
+        // SAFETY: the slot has just been unlinked from the free list, so no other
+        // `FrameBuffer` refers to it until this one is dropped and relinks it.
+        let buf = unsafe { &mut *self.buffers[index].get() };
+        Some(FrameBuffer {
+            buf,
+            slot: &self.next_free[index],
+            head: &self.head,
+            index,
+        })
+    }
+
+    /// The number of buffers currently available to [`FramePool::alloc`].
+    pub fn available(&self) -> usize {
+        let mut count = 0;
+        let mut next = self.head.get();
+        while let Some(i) = next {
+            count += 1;
+            next = self.next_free[i].get();
+        }
+        count
+    }
+}
+
+impl<const N: usize, const FRAME_SIZE: usize> Default for FramePool<N, FRAME_SIZE> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A buffer borrowed from a [`FramePool`]. Dereferences to `[u8]`, so it can be passed
+/// straight to [`crate::driver::Chip::tx`] and [`crate::driver::Chip::rx`].
+pub struct FrameBuffer<'a, const FRAME_SIZE: usize> {
+    buf: &'a mut [u8; FRAME_SIZE],
+    slot: &'a Cell<Option<usize>>,
+    head: &'a Cell<Option<usize>>,
+    index: usize,
+}
+
+impl<const FRAME_SIZE: usize> Deref for FrameBuffer<'_, FRAME_SIZE> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.buf
+    }
+}
+
+impl<const FRAME_SIZE: usize> DerefMut for FrameBuffer<'_, FRAME_SIZE> {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        self.buf
+    }
+}
+
+impl<const FRAME_SIZE: usize> AsRef<[u8]> for FrameBuffer<'_, FRAME_SIZE> {
+    fn as_ref(&self) -> &[u8] {
+        self.buf
+    }
+}
+
+impl<const FRAME_SIZE: usize> AsMut<[u8]> for FrameBuffer<'_, FRAME_SIZE> {
+    fn as_mut(&mut self) -> &mut [u8] {
+        self.buf
+    }
+}
+
+impl<const FRAME_SIZE: usize> Drop for FrameBuffer<'_, FRAME_SIZE> {
+    fn drop(&mut self) {
+        self.slot.set(self.head.get());
+        self.head.set(Some(self.index));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alloc_until_exhausted() {
+        let pool = FramePool::<3, 16>::new();
+        let a = pool.alloc().unwrap();
+        let b = pool.alloc().unwrap();
+        let c = pool.alloc().unwrap();
+        assert!(pool.alloc().is_none());
+        let indices = [a.index, b.index, c.index];
+        assert_eq!(indices, [0, 1, 2]);
+    }
+
+    #[test]
+    fn drop_returns_slot() {
+        let pool = FramePool::<2, 16>::new();
+        let a = pool.alloc().unwrap();
+        let mut b = pool.alloc().unwrap();
+        b[0] = 0xaa;
+        let b_index = b.index;
+        drop(b);
+        let b = pool.alloc().unwrap();
+        assert_eq!(b.index, b_index);
+        // The contents are left as they were
+        assert_eq!(b[0], 0xaa);
+        drop(a);
+        drop(b);
+        assert_eq!(pool.available(), 2);
+    }
+
+    #[test]
+    fn buffers_dont_alias() {
+        let pool = FramePool::<2, 4>::new();
+        let mut a = pool.alloc().unwrap();
+        let mut b = pool.alloc().unwrap();
+        a.fill(1);
+        b.fill(2);
+        assert_eq!(&a[..], &[1; 4]);
+        assert_eq!(&b[..], &[2; 4]);
+        assert_eq!(a.len(), 4);
+    }
+
+    #[test]
+    fn available() {
+        let pool = FramePool::<4, 8>::default();
+        assert_eq!(pool.available(), 4);
+        let a = pool.alloc().unwrap();
+        let b = pool.alloc().unwrap();
+        assert_eq!(pool.available(), 2);
+        drop(a);
+        assert_eq!(pool.available(), 3);
+        drop(b);
+        assert_eq!(pool.available(), 4);
+    }
+
+    #[test]
+    fn empty_pool() {
+        let pool = FramePool::<0, 8>::new();
+        assert_eq!(pool.available(), 0);
+        assert!(pool.alloc().is_none());
+    }
+}
@@ -0,0 +1,10 @@
+//! The commonly used types, for glob importing with `use ksz8851snl::prelude::*;`
+pub use crate::device::SpiRxDataBurstLength;
+pub use crate::driver::{
+    BlockingChip, Chip, ChipInfo, CollisionMode, ConnectionState, Duplex, EnergyDetectDelay, Error,
+    HealthReport, InitChip, InitConfig, InterruptMask, InterruptStatus, LinkSpeed, MacAddress,
+    MdixMode, MulticastFilter, PhyStatus, QosPriority, RegisterTestResults, RxFilterConfig,
+    RxFrameInfo, RxFrameMetrics, SpiLoopbackResult, TxCompletionStatus, UnicastFilter, UninitChip,
+    WakeupConfig, WakeupEvent, WakeupFramePattern, WakeupPattern,
+};
+pub use crate::pool::{FrameBuffer, FramePool};
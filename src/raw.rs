@@ -0,0 +1,118 @@
+//! Low level SPI command encoding, for users building their own SPI transactions.
+//!
+//! Everything in [`crate::driver::Chip`] is built on these. Using them directly bypasses
+//! all of the driver's sequencing (interrupt masking, DMA gating via `RXQCR::sda`, frame
+//! pointer handling...), and getting that wrong can leave the chip's queues in a corrupt
+//! state that only a reset recovers from.
+
+/// The SPI opcode, sent in the top two bits of the first byte of every transaction.
+#[repr(u8)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Opcode {
+    /// Internal I/O register read
+    RegRead = 0b00,
+    /// Internal I/O register write
+    RegWrite = 0b01,
+    /// RXQ FIFO read
+    RXRead = 0b10,
+    /// TXQ FIFO write
+    TXWrite = 0b11,
+}
+
+/// Build the 2-byte command for a register access of `count` bytes at `addr`.
+///
+/// # Panics
+/// If the access isn't one the chip supports: a single byte at any address, or 16 bits
+/// at a 2-aligned address.
+pub fn reg_cmd(o: Opcode, addr: u8, count: u8) -> [u8; 2] {
+    // The device only supports accessing 4-aligned addresses, with selectable bytes
+    // being read/written ("byte enables").
+    let byte_enable = match (addr & 0b11, count) {
+        (0, 1) => 0b0001,
+        (1, 1) => 0b0010,
+        (2, 1) => 0b0100,
+        (3, 1) => 0b1000,
+        (0, 2) => 0b0011,
+        (2, 2) => 0b1100,
+        (_, _) => unimplemented!(),
+    };
+    [
+        ((o as u8) << 6) | (byte_enable << 2) | (addr >> 6),
+        (addr & 0b00111100) << 2,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn byte_enables() {
+        // (address, count, expected command) for a register read
+        let cases = [
+            (0x10, 1, [0b0000_0100, 0x40]),
+            (0x11, 1, [0b0000_1000, 0x40]),
+            (0x12, 1, [0b0001_0000, 0x40]),
+            (0x13, 1, [0b0010_0000, 0x40]),
+            (0x10, 2, [0b0000_1100, 0x40]),
+            (0x12, 2, [0b0011_0000, 0x40]),
+        ];
+        for (addr, count, expected) in cases {
+            assert_eq!(
+                reg_cmd(Opcode::RegRead, addr, count),
+                expected,
+                "{addr:#04x}, {count} bytes"
+            );
+        }
+    }
+
+    #[test]
+    fn register_addresses() {
+        // A selection of real registers, as 16-bit reads
+        let cases = [
+            (0x10, [0x0c, 0x40]), // MARL
+            (0x70, [0x0d, 0xc0]), // TXCR
+            (0x82, [0x32, 0x00]), // RXQCR
+            (0x90, [0x0e, 0x40]), // IER
+            (0x92, [0x32, 0x40]), // ISR
+            (0xc0, [0x0f, 0x00]), // CIDER
+            (0xd4, [0x0f, 0x50]), // PMECR
+            (0xe4, [0x0f, 0x90]), // P1MBCR
+        ];
+        for (addr, expected) in cases {
+            assert_eq!(reg_cmd(Opcode::RegRead, addr, 2), expected, "{addr:#04x}");
+        }
+    }
+
+    // Decode a command back into (opcode bits, address of the first enabled byte, count)
+    fn decode(cmd: [u8; 2]) -> (u8, u8, u8) {
+        let byte_enable = (cmd[0] >> 2) & 0b1111;
+        let word = ((cmd[0] & 0b11) << 6) | ((cmd[1] >> 2) & 0b0011_1100);
+        (
+            cmd[0] >> 6,
+            word + byte_enable.trailing_zeros() as u8,
+            byte_enable.count_ones() as u8,
+        )
+    }
+
+    #[test]
+    fn every_address_round_trips() {
+        for o in [Opcode::RegRead, Opcode::RegWrite] {
+            for addr in 0..=u8::MAX {
+                assert_eq!(decode(reg_cmd(o, addr, 1)), (o as u8, addr, 1));
+                if addr % 2 == 0 {
+                    assert_eq!(decode(reg_cmd(o, addr, 2)), (o as u8, addr, 2));
+                }
+                // The low 2 bits of the second byte are always zero
+                assert_eq!(reg_cmd(o, addr, 1)[1] & 0b11, 0);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn unaligned_16_bit_access() {
+        reg_cmd(Opcode::RegRead, 0x11, 2);
+    }
+}
@@ -0,0 +1,8 @@
+//! Low-level register field set types for advanced users.
+//!
+//! These are the types read and written through [`crate::driver::Chip::dev`], e.g.
+//! `chip.dev.rxfhsr().read_async()` returns an [`Rxfhsr`].
+pub use crate::device::field_sets::*;
+pub use crate::device::{
+    Ocbcds, OutputPinDriveStrength, PowerManagementMode, SpiRxDataBurstLength,
+};